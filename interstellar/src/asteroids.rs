@@ -0,0 +1,260 @@
+// Asteroid belts: thousands of small rocks generated from a seed, all instances of the same
+// displaced-sphere shape (`asteroid_radius`), found through a bounding volume hierarchy.
+//
+// A rock only stores where it is, its size, its seed and how it spins; the shape and the
+// surface come from the belt's `asteroid` params and the rock seed when a ray gets close.
+
+use crate::math::*;
+use crate::noise::{hash_lattice, unit_f32};
+use crate::scene::{march_displaced, ray_sphere, SceneBody, Transform};
+use crate::shader::*;
+
+// A ring of rocks around `center` in the world XZ plane.
+#[derive(Copy, Clone)]
+pub struct Belt {
+    pub center: Vec3,
+    pub rin: f32,
+    pub rout: f32,
+    pub thickness: f32, // media altura sobre el plano
+    pub count: u32,
+    pub size_min: f32,
+    pub size_max: f32,
+    pub spin: f32, // rad por unidad de t, la más rápida
+    pub seed: u64,
+    pub params: Params,
+}
+
+impl Belt {
+    pub fn new(params: Params) -> Self {
+        Self {
+            center: vec3(0.0, 0.0, 0.0),
+            rin: 2.0,
+            rout: 3.0,
+            thickness: 0.05,
+            count: 1000,
+            size_min: 0.005,
+            size_max: 0.04,
+            spin: 1.0,
+            seed: 0,
+            params,
+        }
+    }
+}
+
+// One instance: a rock of `radius` at `center`, turning about `axis`.
+#[derive(Copy, Clone, Debug)]
+pub struct Rock {
+    pub center: Vec3,
+    pub radius: f32,
+    pub seed: u64,
+    pub axis: Vec3,
+    pub spin: f32,
+    pub belt: usize,
+}
+
+impl Rock {
+    pub fn transform(&self, t: f32) -> Transform {
+        Transform { position: self.center, rotation: Mat3::rotation_axis(self.axis, self.spin * t) }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Asteroids {
+    pub belts: Vec<Belt>,
+    pub rocks: Vec<Rock>,
+    bvh: Bvh,
+}
+
+impl Asteroids {
+    pub fn new(belts: Vec<Belt>) -> Self {
+        let rocks: Vec<Rock> = belts.iter().enumerate().flat_map(|(i, b)| generate(b, i)).collect();
+        let bvh = Bvh::build(&rocks);
+        Self { belts, rocks, bvh }
+    }
+
+    // Nearest rock closer than `t_max` at time `time`: (rock index, t).
+    pub fn intersect(&self, ro: Vec3, rd: Vec3, t_max: f32, time: f32) -> Option<(usize, f32)> {
+        self.bvh.intersect(ro, rd, t_max, |i| self.hit(i, ro, rd, time))
+    }
+
+    // Where the ray meets rock `i` at time `time`, if it does.
+    fn hit(&self, i: usize, ro: Vec3, rd: Vec3, time: f32) -> Option<f32> {
+        let r = &self.rocks[i];
+        ray_sphere(ro, rd, r.center, r.radius)?;
+        let tr = r.transform(time);
+        let p = &self.belts[r.belt].params.asteroid;
+        let (o, d) = (tr.to_local(ro) / r.radius, tr.dir_to_local(rd));
+        let t = march_displaced(o, d, |n| asteroid_radius(n, r.seed, p), |n| asteroid_radius_coarse(n, r.seed, p))?;
+        Some(t * r.radius)
+    }
+
+    // The rock as a body at time `time`, to shade it like any other.
+    pub fn body(&self, i: usize, time: f32) -> SceneBody {
+        let r = &self.rocks[i];
+        let mut b = SceneBody::new(Body::Asteroid, r.center, r.radius, self.belts[r.belt].params);
+        b.transform = r.transform(time);
+        b.seed = r.seed;
+        b
+    }
+}
+
+// Rocks of belt number `index`; the same belt always gives the same rocks.
+fn generate(b: &Belt, index: usize) -> Vec<Rock> {
+    (0..b.count as i64)
+        .map(|i| {
+            let u = |k: i64| unit_f32(hash_lattice(&[i, k], b.seed));
+            // uniforme en área entre rin y rout, alturas concentradas en el plano
+            let r = mix(b.rin*b.rin, b.rout*b.rout, u(0)).sqrt();
+            let a = 2.0 * PI * u(1);
+            let y = b.thickness * (u(2) + u(3) + u(4) - 1.5) / 1.5;
+            // tamaños con N(>R) ∝ R^-2 entre size_min y size_max: muchas piedras pequeñas
+            let k = (b.size_min / b.size_max).powi(2);
+            let radius = b.size_min / (1.0 - u(5) * (1.0 - k)).sqrt();
+            let z = 2.0*u(6) - 1.0;
+            let phi = 2.0 * PI * u(7);
+            let axis = vec3((1.0 - z*z).sqrt() * phi.cos(), z, (1.0 - z*z).sqrt() * phi.sin());
+            Rock {
+                center: b.center + vec3(r * a.cos(), y, r * a.sin()),
+                radius,
+                seed: hash_lattice(&[i, 8], b.seed),
+                axis,
+                spin: b.spin * (2.0*u(9) - 1.0),
+                belt: index,
+            }
+        })
+        .collect()
+}
+
+// --- Bounding volume hierarchy ----------------------------------------------
+// Boxes around the rocks' bounding spheres, split at the median of the longest axis.
+
+const LEAF_SIZE: usize = 4;
+
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    min: Vec3,
+    max: Vec3,
+    // hoja: `order[first..first + count]`; interno (count = 0): hijos en first y first + 1
+    first: u32,
+    count: u32,
+}
+
+#[derive(Clone, Default)]
+struct Bvh {
+    nodes: Vec<Node>,
+    order: Vec<u32>,
+}
+
+impl Bvh {
+    fn build(rocks: &[Rock]) -> Self {
+        let mut order: Vec<u32> = (0..rocks.len() as u32).collect();
+        let mut nodes = Vec::new();
+        if !rocks.is_empty() {
+            nodes.push(Node { min: vec3(0.0, 0.0, 0.0), max: vec3(0.0, 0.0, 0.0), first: 0, count: 0 });
+            build_node(&mut nodes, 0, &mut order, 0, rocks);
+        }
+        Self { nodes, order }
+    }
+
+    // Nearest hit reported by `hit(rock)` among the rocks whose boxes the ray crosses.
+    fn intersect(&self, ro: Vec3, rd: Vec3, mut t_max: f32, hit: impl Fn(usize) -> Option<f32>) -> Option<(usize, f32)> {
+        if self.nodes.is_empty() { return None; }
+        let inv = vec3(1.0 / rd.x, 1.0 / rd.y, 1.0 / rd.z);
+        let mut best = None;
+        let mut stack = [0u32; 64];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let node = &self.nodes[stack[top] as usize];
+            if !slab(ro, inv, node.min, node.max, t_max) { continue; }
+            if node.count > 0 {
+                for &i in &self.order[node.first as usize..(node.first + node.count) as usize] {
+                    if let Some(t) = hit(i as usize).filter(|&t| t < t_max) {
+                        t_max = t;
+                        best = Some((i as usize, t));
+                    }
+                }
+            } else {
+                stack[top] = node.first;
+                stack[top + 1] = node.first + 1;
+                top += 2;
+            }
+        }
+        best
+    }
+}
+
+fn build_node(nodes: &mut Vec<Node>, index: usize, items: &mut [u32], first: usize, rocks: &[Rock]) {
+    let mut min = vec3(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = vec3(f32::MIN, f32::MIN, f32::MIN);
+    for &i in items.iter() {
+        let r = &rocks[i as usize];
+        let e = vec3(r.radius, r.radius, r.radius);
+        let (lo, hi) = (r.center - e, r.center + e);
+        min = vec3(min.x.min(lo.x), min.y.min(lo.y), min.z.min(lo.z));
+        max = vec3(max.x.max(hi.x), max.y.max(hi.y), max.z.max(hi.z));
+    }
+    if items.len() <= LEAF_SIZE {
+        nodes[index] = Node { min, max, first: first as u32, count: items.len() as u32 };
+        return;
+    }
+
+    let size = max - min;
+    let axis = |p: Vec3| if size.x >= size.y && size.x >= size.z { p.x } else if size.y >= size.z { p.y } else { p.z };
+    let mid = items.len() / 2;
+    items.select_nth_unstable_by(mid, |a, b| axis(rocks[*a as usize].center).total_cmp(&axis(rocks[*b as usize].center)));
+
+    let left = nodes.len();
+    nodes.push(nodes[index]);
+    nodes.push(nodes[index]);
+    nodes[index] = Node { min, max, first: left as u32, count: 0 };
+    let (l, r) = items.split_at_mut(mid);
+    build_node(nodes, left, l, first, rocks);
+    build_node(nodes, left + 1, r, first + mid, rocks);
+}
+
+// Does the ray enter the box before `t_max`?
+#[inline]
+fn slab(ro: Vec3, inv: Vec3, min: Vec3, max: Vec3, t_max: f32) -> bool {
+    let (x0, x1) = ((min.x - ro.x) * inv.x, (max.x - ro.x) * inv.x);
+    let (y0, y1) = ((min.y - ro.y) * inv.y, (max.y - ro.y) * inv.y);
+    let (z0, z1) = ((min.z - ro.z) * inv.z, (max.z - ro.z) * inv.z);
+    let near = x0.min(x1).max(y0.min(y1)).max(z0.min(z1));
+    let far = x0.max(x1).min(y0.max(y1)).min(z0.max(z1));
+    near <= far && far > 0.0 && near < t_max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The hierarchy must find exactly the nearest rock a loop over all of them finds.
+    #[test]
+    fn bvh_matches_brute_force() {
+        let mut belt = Belt::new(Params::default());
+        belt.count = 2000;
+        belt.thickness = 0.2;
+        belt.size_max = 0.08;
+        belt.seed = 7;
+        let a = Asteroids::new(vec![belt]);
+        let mut hits = 0;
+        for k in 0..300u64 {
+            let h = hash_lattice(&[k as i64], 1);
+            let u = |s: u32| unit_f32(h.rotate_left(s)) * 2.0 - 1.0;
+            let ro = vec3(u(0), u(11), u(22)).normalized() * 4.0;
+            // la mitad de los rayos apuntan a una roca, el resto a cualquier parte
+            let target = if k % 2 == 0 { a.rocks[(h % 2000) as usize].center } else { vec3(u(33), u(44), u(55)) * 3.0 };
+            let rd = (target - ro).normalized();
+            let time = u(7) * 3.0;
+            let mut want: Option<(usize, f32)> = None;
+            for i in 0..a.rocks.len() {
+                if let Some(t) = a.hit(i, ro, rd, time).filter(|&t| want.is_none_or(|(_, b)| t < b)) {
+                    want = Some((i, t));
+                }
+            }
+            assert_eq!(a.intersect(ro, rd, f32::MAX, time), want, "ray {k}");
+            hits += want.is_some() as u32;
+        }
+        assert!(hits > 100, "only {hits} rays hit a rock");
+    }
+}
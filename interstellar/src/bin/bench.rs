@@ -0,0 +1,45 @@
+// ms/frame per body type, single-threaded vs tiled multithreaded.
+//   cargo run --release --bin bench -- [frames] [threads]
+use std::time::Instant;
+
+use interstellar::*;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    // at least one frame, otherwise nothing is drawn and the buffers compare equal trivially
+    let frames: usize = args.next().and_then(|a| a.parse().ok()).unwrap_or(10).max(1);
+    let (width, height) = (800, 600);
+    let mut multi = Renderer::new(width, height);
+    if let Some(n) = args.next().and_then(|a| a.parse().ok()) { multi = multi.with_threads(n); }
+    let single = multi.with_threads(1);
+
+    let params = Params::default();
+    let cam = Camera::default();
+    let mut a = vec![0u32; width * height];
+    let mut b = vec![0u32; width * height];
+
+    println!("{}x{}, {} frames, {} threads", width, height, frames, multi.threads);
+    println!("{:<16} {:>12} {:>12} {:>8}  identical", "body", "1 thread", "tiled", "speedup");
+    let mut row = |name: &str, draw: &dyn Fn(&Renderer, f32, &mut [u32])| {
+        let time = |r: &Renderer, buf: &mut [u32]| {
+            let start = Instant::now();
+            for i in 0..frames { draw(r, i as f32 * 0.01, buf); }
+            start.elapsed().as_secs_f64() * 1000.0 / frames as f64
+        };
+        let ms1 = time(&single, &mut a);
+        let msn = time(&multi, &mut b);
+        println!("{:<16} {:>9.2} ms {:>9.2} ms {:>7.2}x  {}", name, ms1, msn, ms1 / msn, if a == b { "yes" } else { "NO" });
+    };
+    for body in Body::ALL {
+        row(body.name(), &|r, t, buf| r.render(&cam, &params, body, t, buf));
+    }
+
+    // gigante gaseoso dentro de un cinturón de 10k rocas
+    let mut scene = Scene::single(Body::GasGiant, params, false, Camera { radius: 7.0, zoom: 0.45, ..cam });
+    scene.asteroids = Asteroids::new(vec![Belt { rin: 1.8, rout: 2.8, count: 10_000, ..Belt::new(params) }]);
+    row("belt (10k)", &|r, t, buf| r.render_scene(&scene, t, buf));
+
+    // dentro del cinturón, con las rocas cubriendo buena parte de la imagen
+    let close = Scene { camera: Camera { pitch: 0.25, radius: 0.6, zoom: 1.0, target: Vec3::new(2.3, 0.0, 0.0), ..cam }, ..scene.clone() };
+    row("belt close-up", &|r, t, buf| r.render_scene(&close, t, buf));
+}
@@ -0,0 +1,1086 @@
+// Scene / params text format, TOML-like:
+//
+//   # comment
+//   [rocky]                 top-level params, defaults for every body
+//   bioma_freq = 7
+//   c_ocean = "#1c3b6b"     colors: "#rrggbb" or [r, g, b]
+//   basis = "simplex"       noise: "value", "gradient" or "simplex"
+//   [clouds]                optional: present (even empty) = cloud layer on
+//
+//   [camera]
+//   yaw = 0
+//   target = [0, 0, 0]
+//
+//   [background]            starfield, Milky Way and nebulae (0 turns a layer off)
+//   seed = 1
+//   pole = [0.3, 0.8, -0.5]
+//
+//   [[light]]
+//   directional = [0, 0.15, 1]    or point = [x, y, z]
+//
+//   [[body]]
+//   kind = "gas_giant"
+//   position = [0, 0, 0]
+//   radius = 1
+//   rings = true
+//   gas.k_bands = 12        per-body override of a param
+//
+//   [[belt]]                asteroid belt, rocks generated from `seed`
+//   center = [0, 0, 0]
+//   rin = 2
+//   rout = 3
+//   count = 10000
+//   asteroid.roughness = 0.5
+//
+// Writing then parsing gives back exactly the same values.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::asteroids::{Asteroids, Belt};
+use crate::math::*;
+use crate::noise::{Metric, NoiseBasis};
+use crate::render::Camera;
+use crate::scene::*;
+use crate::shader::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub col: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.col, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn err<T>(line: usize, col: usize, msg: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError { line, col, msg: msg.into() })
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError { fn from(e: io::Error) -> Self { LoadError::Io(e) } }
+impl From<ParseError> for LoadError { fn from(e: ParseError) -> Self { LoadError::Parse(e) } }
+
+// A parsed file: the top-level params plus the scene (which may have no bodies).
+#[derive(Clone)]
+pub struct SceneFile {
+    pub params: Params,
+    pub scene: Scene,
+}
+
+pub fn load_scene(path: impl AsRef<Path>) -> Result<SceneFile, LoadError> {
+    let src = fs::read_to_string(path)?;
+    Ok(parse_scene_file(&src)?)
+}
+
+pub fn save_scene(path: impl AsRef<Path>, scene: &Scene) -> io::Result<()> {
+    fs::write(path, write_scene(scene))
+}
+
+pub fn save_scene_file(path: impl AsRef<Path>, file: &SceneFile) -> io::Result<()> {
+    fs::write(path, write_scene_file(file))
+}
+
+pub fn save_params(path: impl AsRef<Path>, params: &Params) -> io::Result<()> {
+    fs::write(path, write_params(params))
+}
+
+pub fn parse_scene(src: &str) -> Result<Scene, ParseError> {
+    Ok(parse_scene_file(src)?.scene)
+}
+
+pub fn parse_params(src: &str) -> Result<Params, ParseError> {
+    Ok(parse_scene_file(src)?.params)
+}
+
+// ---------------------------------------------------------------------------
+// Params reflection
+
+enum Field<'a> {
+    Num(&'a mut f32),
+    Color(&'a mut Color),
+    Basis(&'a mut NoiseBasis),
+    Metric(&'a mut Metric),
+    Count(&'a mut u32),
+}
+
+// (section, key, field) for every tweakable value in Params; [clouds] only when present
+fn param_fields(p: &mut Params) -> Vec<(&'static str, &'static str, Field<'_>)> {
+    use Field::{Basis as B, Color as C, Count as K, Metric as M, Num as N};
+    let mut fields = vec![
+        ("common", "warm", C(&mut p.common.warm)),
+
+        ("disk", "rin", N(&mut p.disk.rin)),
+        ("disk", "rout", N(&mut p.disk.rout)),
+        ("disk", "bands_w", N(&mut p.disk.bands_w)),
+        ("disk", "bands_phi", N(&mut p.disk.bands_phi)),
+        ("disk", "noise_freq", N(&mut p.disk.noise_freq)),
+        ("disk", "noise_amp", N(&mut p.disk.noise_amp)),
+        ("disk", "beaming", N(&mut p.disk.beaming)),
+        ("disk", "temp_in", N(&mut p.disk.temp_in)),
+        ("disk", "temp_mix", N(&mut p.disk.temp_mix)),
+        ("disk", "basis", B(&mut p.disk.basis)),
+        ("disk", "c1", C(&mut p.disk.c1)),
+        ("disk", "c2", C(&mut p.disk.c2)),
+        ("disk", "c3", C(&mut p.disk.c3)),
+
+        ("rocky", "bioma_freq", N(&mut p.rocky.bioma_freq)),
+        ("rocky", "height_freq", N(&mut p.rocky.height_freq)),
+        ("rocky", "grad_amp", N(&mut p.rocky.grad_amp)),
+        ("rocky", "k_atm", N(&mut p.rocky.k_atm)),
+        ("rocky", "basis", B(&mut p.rocky.basis)),
+        ("rocky", "crater_freq", N(&mut p.rocky.crater_freq)),
+        ("rocky", "crater_depth", N(&mut p.rocky.crater_depth)),
+        ("rocky", "sea_level", N(&mut p.rocky.sea_level)),
+        ("rocky", "shininess", N(&mut p.rocky.shininess)),
+        ("rocky", "city_lights", N(&mut p.rocky.city_lights)),
+        ("rocky", "city_freq", N(&mut p.rocky.city_freq)),
+        ("rocky", "c_land1", C(&mut p.rocky.c_land1)),
+        ("rocky", "c_land2", C(&mut p.rocky.c_land2)),
+        ("rocky", "c_ocean", C(&mut p.rocky.c_ocean)),
+        ("rocky", "c_shallow", C(&mut p.rocky.c_shallow)),
+        ("rocky", "c_city", C(&mut p.rocky.c_city)),
+
+        ("gas", "k_bands", N(&mut p.gas.k_bands)),
+        ("gas", "dist_amp", N(&mut p.gas.dist_amp)),
+        ("gas", "noise_freq", N(&mut p.gas.noise_freq)),
+        ("gas", "storm_speed", N(&mut p.gas.storm_speed)),
+        ("gas", "basis", B(&mut p.gas.basis)),
+        ("gas", "c_a", C(&mut p.gas.c_a)),
+        ("gas", "c_b", C(&mut p.gas.c_b)),
+        ("gas", "c_c", C(&mut p.gas.c_c)),
+
+        ("ice", "freq", N(&mut p.ice.freq)),
+        ("ice", "marbling", N(&mut p.ice.marbling)),
+        ("ice", "basis", B(&mut p.ice.basis)),
+        ("ice", "crack_scale", N(&mut p.ice.crack_scale)),
+        ("ice", "crack_width", N(&mut p.ice.crack_width)),
+        ("ice", "crack_metric", M(&mut p.ice.crack_metric)),
+        ("ice", "k_atm", N(&mut p.ice.k_atm)),
+        ("ice", "c_ice", C(&mut p.ice.c_ice)),
+        ("ice", "c_snow", C(&mut p.ice.c_snow)),
+        ("ice", "c_crack", C(&mut p.ice.c_crack)),
+
+        ("lava", "crack_scale", N(&mut p.lava.crack_scale)),
+        ("lava", "crack_width", N(&mut p.lava.crack_width)),
+        ("lava", "flow_speed", N(&mut p.lava.flow_speed)),
+        ("lava", "magma_temp", N(&mut p.lava.magma_temp)),
+        ("lava", "basis", B(&mut p.lava.basis)),
+        ("lava", "c_crust", C(&mut p.lava.c_crust)),
+        ("lava", "c_magma_cool", C(&mut p.lava.c_magma_cool)),
+        ("lava", "c_magma_hot", C(&mut p.lava.c_magma_hot)),
+
+        ("desert", "dune_wavelength", N(&mut p.desert.dune_wavelength)),
+        ("desert", "wind_dir", N(&mut p.desert.wind_dir)),
+        ("desert", "sharpness", N(&mut p.desert.sharpness)),
+        ("desert", "lakes", N(&mut p.desert.lakes)),
+        ("desert", "mesas", N(&mut p.desert.mesas)),
+        ("desert", "basis", B(&mut p.desert.basis)),
+        ("desert", "c_sand_light", C(&mut p.desert.c_sand_light)),
+        ("desert", "c_sand_dark", C(&mut p.desert.c_sand_dark)),
+        ("desert", "c_salt", C(&mut p.desert.c_salt)),
+        ("desert", "c_rock", C(&mut p.desert.c_rock)),
+
+        ("moon", "crater_freq", N(&mut p.moon.crater_freq)),
+        ("moon", "crater_octaves", K(&mut p.moon.crater_octaves)),
+        ("moon", "size_power", N(&mut p.moon.size_power)),
+        ("moon", "density", N(&mut p.moon.density)),
+        ("moon", "rim_height", N(&mut p.moon.rim_height)),
+        ("moon", "ray_ejecta", N(&mut p.moon.ray_ejecta)),
+        ("moon", "relief", N(&mut p.moon.relief)),
+        ("moon", "maria", N(&mut p.moon.maria)),
+        ("moon", "albedo", N(&mut p.moon.albedo)),
+        ("moon", "basis", B(&mut p.moon.basis)),
+        ("moon", "c_highland", C(&mut p.moon.c_highland)),
+        ("moon", "c_mare", C(&mut p.moon.c_mare)),
+        ("moon", "c_ray", C(&mut p.moon.c_ray)),
+
+        ("comet", "roughness", N(&mut p.comet.roughness)),
+        ("comet", "coma_radius", N(&mut p.comet.coma_radius)),
+        ("comet", "coma_brightness", N(&mut p.comet.coma_brightness)),
+        ("comet", "tail_length", N(&mut p.comet.tail_length)),
+        ("comet", "ion_brightness", N(&mut p.comet.ion_brightness)),
+        ("comet", "dust_brightness", N(&mut p.comet.dust_brightness)),
+        ("comet", "dust_curvature", N(&mut p.comet.dust_curvature)),
+        ("comet", "ref_distance", N(&mut p.comet.ref_distance)),
+        ("comet", "basis", B(&mut p.comet.basis)),
+        ("comet", "c_nucleus", C(&mut p.comet.c_nucleus)),
+        ("comet", "c_coma", C(&mut p.comet.c_coma)),
+        ("comet", "c_ion", C(&mut p.comet.c_ion)),
+        ("comet", "c_dust", C(&mut p.comet.c_dust)),
+
+        ("asteroid", "roughness", N(&mut p.asteroid.roughness)),
+        ("asteroid", "lump_freq", N(&mut p.asteroid.lump_freq)),
+        ("asteroid", "elongation", N(&mut p.asteroid.elongation)),
+        ("asteroid", "crater_freq", N(&mut p.asteroid.crater_freq)),
+        ("asteroid", "crater_depth", N(&mut p.asteroid.crater_depth)),
+        ("asteroid", "albedo", N(&mut p.asteroid.albedo)),
+        ("asteroid", "basis", B(&mut p.asteroid.basis)),
+        ("asteroid", "c_rock", C(&mut p.asteroid.c_rock)),
+        ("asteroid", "c_dust", C(&mut p.asteroid.c_dust)),
+
+        ("atmosphere", "radius", N(&mut p.atmosphere.radius)),
+        ("atmosphere", "h_rayleigh", N(&mut p.atmosphere.h_rayleigh)),
+        ("atmosphere", "h_mie", N(&mut p.atmosphere.h_mie)),
+        ("atmosphere", "beta_rayleigh", C(&mut p.atmosphere.beta_rayleigh)),
+        ("atmosphere", "beta_mie", N(&mut p.atmosphere.beta_mie)),
+        ("atmosphere", "mie_g", N(&mut p.atmosphere.mie_g)),
+        ("atmosphere", "sun", N(&mut p.atmosphere.sun)),
+
+        ("ring", "rin", N(&mut p.ring.rin)),
+        ("ring", "rout", N(&mut p.ring.rout)),
+        ("ring", "opacity", N(&mut p.ring.opacity)),
+        ("ring", "ringlets", N(&mut p.ring.ringlets)),
+        ("ring", "gap_pos", N(&mut p.ring.gap_pos)),
+        ("ring", "gap_width", N(&mut p.ring.gap_width)),
+        ("ring", "basis", B(&mut p.ring.basis)),
+        ("ring", "c_inner", C(&mut p.ring.c_inner)),
+        ("ring", "c_outer", C(&mut p.ring.c_outer)),
+
+        ("star", "temperature", N(&mut p.star.temperature)),
+        ("star", "granulation", N(&mut p.star.granulation)),
+        ("star", "spot_density", N(&mut p.star.spot_density)),
+        ("star", "flare_activity", N(&mut p.star.flare_activity)),
+        ("star", "basis", B(&mut p.star.basis)),
+    ];
+    if let Some(c) = &mut p.clouds {
+        fields.extend([
+            ("clouds", "coverage", N(&mut c.coverage)),
+            ("clouds", "altitude", N(&mut c.altitude)),
+            ("clouds", "scale", N(&mut c.scale)),
+            ("clouds", "wind", N(&mut c.wind)),
+            ("clouds", "wind_mid", N(&mut c.wind_mid)),
+            ("clouds", "bands", N(&mut c.bands)),
+            ("clouds", "octaves", K(&mut c.octaves)),
+            ("clouds", "basis", B(&mut c.basis)),
+            ("clouds", "color", C(&mut c.color)),
+        ]);
+    }
+    fields
+}
+
+fn is_param_section(name: &str) -> bool {
+    let mut p = Params { clouds: Some(CloudParams::default()), ..Params::default() };
+    param_fields(&mut p).iter().any(|(s, _, _)| *s == name)
+}
+
+fn set_param(p: &mut Params, section: &str, key: &str, e: &Entry) -> Result<(), ParseError> {
+    // any [clouds] key turns the layer on, starting from the default clouds
+    if section == "clouds" && p.clouds.is_none() {
+        p.clouds = Some(CloudParams::default());
+    }
+    for (s, k, field) in param_fields(p) {
+        if s != section || k != key { continue; }
+        match field {
+            Field::Num(x) => *x = e.num()?,
+            Field::Color(c) => *c = e.color()?,
+            Field::Basis(b) => *b = e.basis()?,
+            Field::Metric(m) => *m = e.metric()?,
+            Field::Count(n) => *n = e.count()?,
+        }
+        return Ok(());
+    }
+    err(e.line, e.key_col, format!("unknown parameter `{}.{}`", section, key))
+}
+
+// ---------------------------------------------------------------------------
+// Document model
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Num(f32),
+    Int(u64),
+    Str(String),
+    Bool(bool),
+    Array(Vec<f32>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Num(_) => "a number",
+            Value::Int(_) => "an integer",
+            Value::Str(_) => "a string",
+            Value::Bool(_) => "a boolean",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+struct Entry {
+    key: String,
+    value: Value,
+    line: usize,
+    key_col: usize,
+    col: usize,
+}
+
+impl Entry {
+    fn expected<T>(&self, what: &str) -> Result<T, ParseError> {
+        err(self.line, self.col, format!("`{}` expects {}, found {}", self.key, what, self.value.kind()))
+    }
+
+    fn num(&self) -> Result<f32, ParseError> {
+        match self.value {
+            Value::Num(x) => Ok(x),
+            Value::Int(n) => Ok(n as f32),
+            _ => self.expected("a number"),
+        }
+    }
+
+    fn uint(&self) -> Result<u64, ParseError> {
+        match self.value { Value::Int(n) => Ok(n), _ => self.expected("a non-negative integer") }
+    }
+
+    fn count(&self) -> Result<u32, ParseError> {
+        match self.value {
+            Value::Int(n) => u32::try_from(n).or_else(|_| self.expected("a small non-negative integer")),
+            _ => self.expected("a non-negative integer"),
+        }
+    }
+
+    fn boolean(&self) -> Result<bool, ParseError> {
+        match self.value { Value::Bool(b) => Ok(b), _ => self.expected("true or false") }
+    }
+
+    fn str(&self) -> Result<&str, ParseError> {
+        match &self.value { Value::Str(s) => Ok(s), _ => self.expected("a string") }
+    }
+
+    fn array(&self, n: usize) -> Result<&[f32], ParseError> {
+        match &self.value {
+            Value::Array(a) if a.len() == n => Ok(a),
+            Value::Array(a) => err(self.line, self.col, format!("`{}` expects {} numbers, found {}", self.key, n, a.len())),
+            _ => self.expected(&format!("an array of {} numbers", n)),
+        }
+    }
+
+    fn basis(&self) -> Result<NoiseBasis, ParseError> {
+        let name = self.str()?;
+        NoiseBasis::from_name(name).map_or_else(
+            || err(self.line, self.col, format!("unknown noise basis \"{}\" (value, gradient, simplex)", name)),
+            Ok,
+        )
+    }
+
+    fn metric(&self) -> Result<Metric, ParseError> {
+        let name = self.str()?;
+        Metric::from_name(name).map_or_else(
+            || err(self.line, self.col, format!("unknown metric \"{}\" (euclidean, manhattan, chebyshev)", name)),
+            Ok,
+        )
+    }
+
+    fn vec3(&self) -> Result<Vec3, ParseError> {
+        let a = self.array(3)?;
+        Ok(vec3(a[0], a[1], a[2]))
+    }
+
+    fn color(&self) -> Result<Color, ParseError> {
+        match &self.value {
+            Value::Str(s) => {
+                let h = s.strip_prefix('#').unwrap_or("");
+                if h.len() != 6 || !h.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return err(self.line, self.col, format!("invalid color \"{}\", expected \"#rrggbb\"", s));
+                }
+                Ok(hex_rgb_u8(s))
+            }
+            Value::Array(_) => self.vec3(),
+            _ => self.expected("a color (\"#rrggbb\" or [r, g, b])"),
+        }
+    }
+}
+
+struct Table {
+    name: String,
+    array: bool,
+    line: usize,
+    col: usize,
+    entries: Vec<Entry>,
+}
+
+impl Table {
+    fn unknown_key<T>(&self, e: &Entry) -> Result<T, ParseError> {
+        err(e.line, e.key_col, format!("unknown key `{}` in [{}]", e.key, self.name))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Lexer (one line at a time, columns are 1-based char positions)
+
+struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Cursor {
+    fn new(src: &str, line: usize) -> Self { Self { chars: src.chars().collect(), pos: 0, line } }
+    fn col(&self) -> usize { self.pos + 1 }
+    fn peek(&self) -> Option<char> { self.chars.get(self.pos).copied() }
+
+    fn fail<T>(&self, msg: impl Into<String>) -> Result<T, ParseError> { err(self.line, self.col(), msg) }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) { self.pos += 1; }
+    }
+
+    fn at_end(&self) -> bool { matches!(self.peek(), None | Some('#')) }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) { self.pos += 1; true } else { false }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        if self.eat(c) { Ok(()) } else { self.fail(format!("expected `{}`", c)) }
+    }
+
+    // only whitespace or a comment may follow
+    fn finish(&mut self) -> Result<(), ParseError> {
+        self.skip_ws();
+        if self.at_end() { Ok(()) } else { self.fail(format!("unexpected `{}`", self.chars[self.pos])) }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '.') { self.pos += 1; }
+        if self.pos == start { return self.fail("expected a name"); }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn number(&mut self) -> Result<f32, ParseError> {
+        let col = self.col();
+        let tok = self.number_token()?;
+        finite(&tok).map_or_else(|| err(self.line, col, format!("invalid number `{}`", tok)), Ok)
+    }
+
+    fn number_token(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-' | '_')) { self.pos += 1; }
+        let tok: String = self.chars[start..self.pos].iter().filter(|c| **c != '_').collect();
+        if tok.is_empty() {
+            return match self.peek() {
+                Some(c) => self.fail(format!("unexpected `{}`", c)),
+                None => self.fail("expected a value"),
+            };
+        }
+        Ok(tok)
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let col = self.col();
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None => return err(self.line, col, "unterminated string"),
+                Some('"') => { self.pos += 1; return Ok(s); }
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some('"') => s.push('"'),
+                        Some('\\') => s.push('\\'),
+                        Some('n') => s.push('\n'),
+                        _ => return self.fail("invalid escape"),
+                    }
+                    self.pos += 1;
+                }
+                Some(c) => { s.push(c); self.pos += 1; }
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some('"') => Ok(Value::Str(self.string()?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_ws();
+                    if self.eat(']') { break; }
+                    items.push(self.number()?);
+                    self.skip_ws();
+                    if self.eat(',') { continue; }
+                    self.expect(']')?;
+                    break;
+                }
+                Ok(Value::Array(items))
+            }
+            Some(c) if c.is_ascii_alphabetic() && self.word_is("true") => { self.pos += 4; Ok(Value::Bool(true)) }
+            Some(c) if c.is_ascii_alphabetic() && self.word_is("false") => { self.pos += 5; Ok(Value::Bool(false)) }
+            _ => {
+                // plain digits stay integers (seeds need all 64 bits)
+                let col = self.col();
+                let tok = self.number_token()?;
+                if tok.bytes().all(|b| b.is_ascii_digit()) {
+                    if let Ok(n) = tok.parse::<u64>() { return Ok(Value::Int(n)); }
+                }
+                finite(&tok).map(Value::Num).map_or_else(|| err(self.line, col, format!("invalid number `{}`", tok)), Ok)
+            }
+        }
+    }
+
+    fn word_is(&self, w: &str) -> bool {
+        let end = self.pos + w.len();
+        end <= self.chars.len()
+            && self.chars[self.pos..end].iter().copied().eq(w.chars())
+            && !matches!(self.chars.get(end), Some(c) if c.is_ascii_alphanumeric() || *c == '_')
+    }
+}
+
+// A number token as f32; nan, inf and anything that overflows to them are not numbers here.
+fn finite(tok: &str) -> Option<f32> {
+    tok.parse::<f32>().ok().filter(|x| x.is_finite())
+}
+
+fn parse_document(src: &str) -> Result<Vec<Table>, ParseError> {
+    let mut tables = vec![Table { name: String::new(), array: false, line: 1, col: 1, entries: Vec::new() }];
+    for (i, raw) in src.lines().enumerate() {
+        let mut c = Cursor::new(raw, i + 1);
+        c.skip_ws();
+        if c.at_end() { continue; }
+
+        if c.eat('[') {
+            let array = c.eat('[');
+            c.skip_ws();
+            let col = c.col();
+            let name = c.ident()?;
+            c.skip_ws();
+            c.expect(']')?;
+            if array { c.expect(']')?; }
+            c.finish()?;
+            tables.push(Table { name, array, line: i + 1, col, entries: Vec::new() });
+            continue;
+        }
+
+        let key_col = c.col();
+        let key = c.ident()?;
+        c.skip_ws();
+        c.expect('=')?;
+        c.skip_ws();
+        let col = c.col();
+        let value = c.value()?;
+        c.finish()?;
+        if let Some(t) = tables.last_mut() {
+            t.entries.push(Entry { key, value, line: i + 1, key_col, col });
+        }
+    }
+    Ok(tables)
+}
+
+// ---------------------------------------------------------------------------
+// Parsing
+
+pub fn parse_scene_file(src: &str) -> Result<SceneFile, ParseError> {
+    let tables = parse_document(src)?;
+
+    // top-level params first: they are the defaults for every [[body]], wherever they appear
+    let mut params = Params::default();
+    for t in tables.iter().filter(|t| !t.array && is_param_section(&t.name)) {
+        if t.name == "clouds" && params.clouds.is_none() {
+            params.clouds = Some(CloudParams::default());
+        }
+        for e in &t.entries {
+            set_param(&mut params, &t.name, &e.key, e)?;
+        }
+    }
+
+    let mut scene = Scene {
+        bodies: Vec::new(),
+        lights: Vec::new(),
+        camera: Camera::default(),
+        asteroids: Asteroids::default(),
+        background: BackgroundParams::default(),
+    };
+    let mut belts = Vec::new();
+    for t in &tables {
+        match (t.name.as_str(), t.array) {
+            ("", false) => {
+                if let Some(e) = t.entries.first() {
+                    return err(e.line, e.key_col, format!("`{}` must be inside a table", e.key));
+                }
+            }
+            ("camera", false) => parse_camera(t, &mut scene.camera)?,
+            ("background", false) => parse_background(t, &mut scene.background)?,
+            ("light", true) => scene.lights.push(parse_light(t)?),
+            ("body", true) => scene.bodies.push(parse_body(t, &params)?),
+            ("belt", true) => belts.push(parse_belt(t, &params)?),
+            (name, false) if is_param_section(name) => {}
+            (name, array) => {
+                let shown = if array { format!("[[{}]]", name) } else { format!("[{}]", name) };
+                return err(t.line, t.col, format!("unknown table {}", shown));
+            }
+        }
+    }
+    scene.asteroids = Asteroids::new(belts);
+    Ok(SceneFile { params, scene })
+}
+
+fn parse_camera(t: &Table, cam: &mut Camera) -> Result<(), ParseError> {
+    for e in &t.entries {
+        match e.key.as_str() {
+            "yaw" => cam.yaw = e.num()?,
+            "pitch" => cam.pitch = e.num()?,
+            "radius" => cam.radius = e.num()?,
+            "zoom" => cam.zoom = e.num()?,
+            "target" => cam.target = e.vec3()?,
+            _ => return t.unknown_key(e),
+        }
+    }
+    Ok(())
+}
+
+fn parse_background(t: &Table, bg: &mut BackgroundParams) -> Result<(), ParseError> {
+    for e in &t.entries {
+        match e.key.as_str() {
+            "seed" => bg.seed = e.uint()?,
+            "stars" => bg.stars = e.num()?,
+            "star_density" => bg.star_density = e.num()?,
+            "milky_way" => bg.milky_way = e.num()?,
+            "pole" => bg.pole = e.vec3()?,
+            "nebulae" => bg.nebulae = e.num()?,
+            "nebula_scale" => bg.nebula_scale = e.num()?,
+            "c_nebula1" => bg.c_nebula1 = e.color()?,
+            "c_nebula2" => bg.c_nebula2 = e.color()?,
+            _ => return t.unknown_key(e),
+        }
+    }
+    Ok(())
+}
+
+fn parse_light(t: &Table) -> Result<Light, ParseError> {
+    let mut light = None;
+    for e in &t.entries {
+        let l = match e.key.as_str() {
+            "directional" => Light::Directional(e.vec3()?),
+            "point" => Light::Point(e.vec3()?),
+            _ => return t.unknown_key(e),
+        };
+        if light.is_some() {
+            return err(e.line, e.key_col, "a light is either `directional` or `point`, not both");
+        }
+        light = Some(l);
+    }
+    match light {
+        Some(l) => Ok(l),
+        None => err(t.line, t.col, "light needs `directional = [x, y, z]` or `point = [x, y, z]`"),
+    }
+}
+
+fn parse_body(t: &Table, defaults: &Params) -> Result<SceneBody, ParseError> {
+    let mut kind = None;
+    let mut b = SceneBody::new(Body::Rocky, vec3(0.0, 0.0, 0.0), 1.0, *defaults);
+    for e in &t.entries {
+        match e.key.as_str() {
+            "kind" => {
+                let name = e.str()?;
+                kind = Some(Body::from_name(name).map_or_else(
+                    || err(e.line, e.col, format!("unknown body kind \"{}\"", name)),
+                    Ok,
+                )?);
+            }
+            "position" => b.transform.position = e.vec3()?,
+            "rotation" => {
+                let m = e.array(9)?;
+                let rows = [vec3(m[0], m[1], m[2]), vec3(m[3], m[4], m[5]), vec3(m[6], m[7], m[8])];
+                // unit rows at right angles, right-handed (no mirroring)
+                let dot = |i: usize, j: usize| rows[i].dot(rows[j]) - if i == j { 1.0 } else { 0.0 };
+                let orthonormal = (0..3).all(|i| (0..3).all(|j| dot(i, j).abs() < 1e-3));
+                if !orthonormal || rows[0].cross(rows[1]).dot(rows[2]) < 0.0 {
+                    return err(e.line, e.col, "`rotation` must be a rotation matrix (orthonormal rows, determinant 1)");
+                }
+                b.transform.rotation = Mat3 { rows };
+            }
+            "radius" => {
+                b.radius = e.num()?;
+                if b.radius <= 0.0 { return err(e.line, e.col, "`radius` must be positive"); }
+            }
+            "seed" => b.seed = e.uint()?,
+            "rings" => b.rings = e.boolean()?,
+            "clouds" => set_clouds(&mut b.params, e)?,
+            key => match key.split_once('.') {
+                Some((section, k)) if is_param_section(section) => set_param(&mut b.params, section, k, e)?,
+                _ => return t.unknown_key(e),
+            },
+        }
+    }
+    b.body = match kind {
+        Some(k) => k,
+        None => return err(t.line, t.col, "body needs a `kind`"),
+    };
+    Ok(b)
+}
+
+// `clouds = false` drops a cloud layer inherited from the top-level params; `true` adds the
+// default one if there is none.
+fn set_clouds(p: &mut Params, e: &Entry) -> Result<(), ParseError> {
+    if !e.boolean()? {
+        p.clouds = None;
+    } else if p.clouds.is_none() {
+        p.clouds = Some(CloudParams::default());
+    }
+    Ok(())
+}
+
+fn parse_belt(t: &Table, defaults: &Params) -> Result<Belt, ParseError> {
+    let mut b = Belt::new(*defaults);
+    for e in &t.entries {
+        match e.key.as_str() {
+            "center" => b.center = e.vec3()?,
+            "rin" => b.rin = e.num()?,
+            "rout" => b.rout = e.num()?,
+            "thickness" => b.thickness = e.num()?,
+            "count" => b.count = e.count()?,
+            "size_min" => b.size_min = e.num()?,
+            "size_max" => b.size_max = e.num()?,
+            "spin" => b.spin = e.num()?,
+            "seed" => b.seed = e.uint()?,
+            "clouds" => set_clouds(&mut b.params, e)?,
+            key => match key.split_once('.') {
+                Some((section, k)) if is_param_section(section) => set_param(&mut b.params, section, k, e)?,
+                _ => return t.unknown_key(e),
+            },
+        }
+    }
+    Ok(b)
+}
+
+// ---------------------------------------------------------------------------
+// Writing
+
+// Rust prints the shortest string that parses back to the same f32.
+fn fmt_num(x: f32) -> String { format!("{}", x) }
+
+fn fmt_vec(v: &[f32]) -> String {
+    let items: Vec<String> = v.iter().map(|x| fmt_num(*x)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+// "#rrggbb" when that reproduces the color exactly, [r, g, b] otherwise
+fn fmt_color(c: Color) -> String {
+    let byte = |x: f32| {
+        let b = (x * 255.0).round();
+        if (0.0..=255.0).contains(&b) && ((b as u8) as f32 / 255.0).to_bits() == x.to_bits() { Some(b as u8) } else { None }
+    };
+    match (byte(c.x), byte(c.y), byte(c.z)) {
+        (Some(r), Some(g), Some(b)) => format!("\"#{:02x}{:02x}{:02x}\"", r, g, b),
+        _ => fmt_vec(&[c.x, c.y, c.z]),
+    }
+}
+
+fn fmt_field(f: &Field<'_>) -> String {
+    match f {
+        Field::Num(x) => fmt_num(**x),
+        Field::Color(c) => fmt_color(**c),
+        Field::Basis(b) => format!("\"{}\"", b.name()),
+        Field::Metric(m) => format!("\"{}\"", m.name()),
+        Field::Count(n) => n.to_string(),
+    }
+}
+
+fn same_field(a: &Field<'_>, b: &Field<'_>) -> bool {
+    match (a, b) {
+        (Field::Num(x), Field::Num(y)) => x.to_bits() == y.to_bits(),
+        (Field::Basis(x), Field::Basis(y)) => x == y,
+        (Field::Metric(x), Field::Metric(y)) => x == y,
+        (Field::Count(x), Field::Count(y)) => x == y,
+        (Field::Color(x), Field::Color(y)) => {
+            x.x.to_bits() == y.x.to_bits() && x.y.to_bits() == y.y.to_bits() && x.z.to_bits() == y.z.to_bits()
+        }
+        _ => false,
+    }
+}
+
+// Every param, grouped in [section] tables.
+pub fn write_params(params: &Params) -> String {
+    let mut p = *params;
+    let mut out = String::new();
+    let mut section = "";
+    for (s, k, f) in param_fields(&mut p) {
+        if s != section {
+            if !out.is_empty() { out.push('\n'); }
+            out += &format!("[{}]\n", s);
+            section = s;
+        }
+        out += &format!("{} = {}\n", k, fmt_field(&f));
+    }
+    out
+}
+
+// Camera, background, lights, bodies and belts. Body and belt params are written only where they differ from the defaults.
+pub fn write_scene(scene: &Scene) -> String {
+    write_scene_over(scene, &Params::default())
+}
+
+// The whole file: top-level params, then the scene with its overrides taken against those params,
+// since that is what they start from when parsed back.
+pub fn write_scene_file(file: &SceneFile) -> String {
+    format!("{}\n{}", write_params(&file.params), write_scene_over(&file.scene, &file.params))
+}
+
+fn write_scene_over(scene: &Scene, defaults: &Params) -> String {
+    let cam = &scene.camera;
+    let mut out = String::new();
+    out += "[camera]\n";
+    out += &format!("yaw = {}\n", fmt_num(cam.yaw));
+    out += &format!("pitch = {}\n", fmt_num(cam.pitch));
+    out += &format!("radius = {}\n", fmt_num(cam.radius));
+    out += &format!("zoom = {}\n", fmt_num(cam.zoom));
+    out += &format!("target = {}\n", fmt_vec(&[cam.target.x, cam.target.y, cam.target.z]));
+
+    let bg = &scene.background;
+    out += "\n[background]\n";
+    out += &format!("seed = {}\n", bg.seed);
+    out += &format!("stars = {}\n", fmt_num(bg.stars));
+    out += &format!("star_density = {}\n", fmt_num(bg.star_density));
+    out += &format!("milky_way = {}\n", fmt_num(bg.milky_way));
+    out += &format!("pole = {}\n", fmt_vec(&[bg.pole.x, bg.pole.y, bg.pole.z]));
+    out += &format!("nebulae = {}\n", fmt_num(bg.nebulae));
+    out += &format!("nebula_scale = {}\n", fmt_num(bg.nebula_scale));
+    out += &format!("c_nebula1 = {}\n", fmt_color(bg.c_nebula1));
+    out += &format!("c_nebula2 = {}\n", fmt_color(bg.c_nebula2));
+
+    for l in &scene.lights {
+        let (key, v) = match *l {
+            Light::Directional(d) => ("directional", d),
+            Light::Point(p) => ("point", p),
+        };
+        out += &format!("\n[[light]]\n{} = {}\n", key, fmt_vec(&[v.x, v.y, v.z]));
+    }
+
+    let mut defaults = *defaults;
+    let defaults = param_fields(&mut defaults);
+    for b in &scene.bodies {
+        let pos = b.transform.position;
+        out += "\n[[body]]\n";
+        out += &format!("kind = \"{}\"\n", b.body.name());
+        out += &format!("position = {}\n", fmt_vec(&[pos.x, pos.y, pos.z]));
+        let rot: Vec<f32> = b.transform.rotation.rows.iter().flat_map(|r| [r.x, r.y, r.z]).collect();
+        let identity: Vec<f32> = Mat3::IDENTITY.rows.iter().flat_map(|r| [r.x, r.y, r.z]).collect();
+        if rot.iter().zip(&identity).any(|(a, b)| a.to_bits() != b.to_bits()) {
+            out += &format!("rotation = {}\n", fmt_vec(&rot));
+        }
+        out += &format!("radius = {}\n", fmt_num(b.radius));
+        out += &format!("seed = {}\n", b.seed);
+        if b.rings { out += "rings = true\n"; }
+
+        out += &write_overrides(&b.params, &defaults);
+    }
+
+    for b in &scene.asteroids.belts {
+        out += "\n[[belt]]\n";
+        out += &format!("center = {}\n", fmt_vec(&[b.center.x, b.center.y, b.center.z]));
+        out += &format!("rin = {}\n", fmt_num(b.rin));
+        out += &format!("rout = {}\n", fmt_num(b.rout));
+        out += &format!("thickness = {}\n", fmt_num(b.thickness));
+        out += &format!("count = {}\n", b.count);
+        out += &format!("size_min = {}\n", fmt_num(b.size_min));
+        out += &format!("size_max = {}\n", fmt_num(b.size_max));
+        out += &format!("spin = {}\n", fmt_num(b.spin));
+        out += &format!("seed = {}\n", b.seed);
+        out += &write_overrides(&b.params, &defaults);
+    }
+    out
+}
+
+// `section.key = value` lines for the params that differ from `defaults`, after a
+// `clouds = false` when the defaults have clouds and these params do not.
+fn write_overrides(params: &Params, defaults: &[(&str, &str, Field<'_>)]) -> String {
+    let mut out = String::new();
+    if params.clouds.is_none() && defaults.iter().any(|(s, _, _)| *s == "clouds") {
+        out += "clouds = false\n";
+    }
+    let mut p = *params;
+    for (s, k, f) in param_fields(&mut p) {
+        let default = defaults.iter().find(|(ds, dk, _)| *ds == s && *dk == k);
+        if !default.is_some_and(|(_, _, d)| same_field(&f, d)) {
+            out += &format!("{}.{} = {}\n", s, k, fmt_field(&f));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every value of a file, floats in their exact (round-trip) debug form.
+    fn snapshot(file: &SceneFile) -> Vec<String> {
+        let params = |p: &Params| -> Vec<String> {
+            let mut p = *p;
+            param_fields(&mut p).iter().map(|(s, k, f)| {
+                let v = match f {
+                    Field::Num(x) => format!("{:?}", x),
+                    Field::Color(c) => format!("{:?}", c),
+                    Field::Basis(b) => b.name().to_string(),
+                    Field::Metric(m) => m.name().to_string(),
+                    Field::Count(n) => n.to_string(),
+                };
+                format!("{}.{} = {}", s, k, v)
+            }).collect()
+        };
+        let scene = &file.scene;
+        let mut out = params(&file.params);
+        out.push(format!("{:?}", scene.camera));
+        out.push(format!("{:?}", scene.background));
+        out.extend(scene.lights.iter().map(|l| format!("{:?}", l)));
+        for b in &scene.bodies {
+            out.push(format!("{} {:?} {:?} {} {}", b.body.name(), b.transform, b.radius, b.seed, b.rings));
+            out.extend(params(&b.params));
+        }
+        for b in &scene.asteroids.belts {
+            out.push(format!(
+                "belt {:?} {:?} {:?} {:?} {} {:?} {:?} {:?} {}",
+                b.center, b.rin, b.rout, b.thickness, b.count, b.size_min, b.size_max, b.spin, b.seed
+            ));
+            out.extend(params(&b.params));
+        }
+        out
+    }
+
+    const SCENE: &str = r#"
+[rocky]
+c_ocean = [0.1, 0.2, 0.3]
+height_freq = 3.7
+
+[clouds]
+coverage = 0.45
+
+[camera]
+yaw = 0.3
+pitch = -0.2
+target = [0.5, 0, 0]
+
+[background]
+seed = 9
+pole = [0.1, 0.9, 0.2]
+c_nebula1 = [0.123, 0.5, 1.7]
+
+[[light]]
+directional = [0, 0.15, 1]
+
+[[body]]
+kind = "gas_giant"
+position = [1, 0, -2]
+radius = 2.5
+seed = 4
+rings = true
+gas.k_bands = 12
+ring.c_inner = [0.91, 0.33, 0.01]
+
+[[body]]
+kind = "rocky"
+rotation = [0, 0, 1, 0, 1, 0, -1, 0, 0]
+rocky.height_freq = 5
+rocky.basis = "gradient"
+
+[[belt]]
+center = [1, 0, -2]
+rin = 4
+rout = 5.5
+count = 300
+seed = 77
+asteroid.roughness = 0.7
+asteroid.c_rock = [0.3, 0.31, 0.333]
+"#;
+
+    #[test]
+    fn scene_file_round_trips() {
+        let file = parse_scene_file(SCENE).unwrap();
+        let written = write_scene_file(&file);
+        let again = parse_scene_file(&written).unwrap();
+        assert_eq!(snapshot(&file), snapshot(&again));
+        assert_eq!(written, write_scene_file(&again));
+        assert!(written.contains("[background]") && written.contains("[[belt]]"));
+        assert!(written.contains("c_ocean = [0.1, 0.2, 0.3]\n"));
+    }
+
+    #[test]
+    fn body_overrides_are_relative_to_the_file_params() {
+        let file = parse_scene_file(SCENE).unwrap();
+        // the second body inherits the file's ocean colour and clouds, and overrides height_freq
+        let rocky = &file.scene.bodies[1];
+        assert_eq!(rocky.params.rocky.height_freq, 5.0);
+        assert_eq!(rocky.params.rocky.c_ocean.y, 0.2);
+        assert!(rocky.params.clouds.is_some());
+        let written = write_scene_file(&file);
+        assert!(written.contains("rocky.height_freq = 5\n"));
+        assert!(!written.contains("rocky.c_ocean"));
+    }
+
+    #[test]
+    fn body_can_turn_off_inherited_clouds() {
+        let src = format!("{}\n[[body]]\nkind = \"ice\"\nclouds = false\n", SCENE);
+        let file = parse_scene_file(&src).unwrap();
+        assert!(file.params.clouds.is_some());
+        assert!(file.scene.bodies.last().unwrap().params.clouds.is_none());
+        let again = parse_scene_file(&write_scene_file(&file)).unwrap();
+        assert_eq!(snapshot(&file), snapshot(&again));
+        assert!(again.scene.bodies.last().unwrap().params.clouds.is_none());
+    }
+
+    #[test]
+    fn out_of_range_values_report_their_position() {
+        let e = parse_scene_file("[[body]]\nkind = \"rocky\"\nradius = 0\n").err().unwrap();
+        assert_eq!((e.line, e.col, e.msg.as_str()), (3, 10, "`radius` must be positive"));
+
+        let e = parse_scene_file("[[body]]\nrotation = [1, 0, 0, 0, 2, 0, 0, 0, 1]\n").err().unwrap();
+        assert_eq!((e.line, e.col), (2, 12));
+        assert!(e.msg.contains("rotation matrix"), "{}", e.msg);
+        // a mirror is orthonormal but not a rotation
+        assert!(parse_scene_file("[[body]]\nkind = \"rocky\"\nrotation = [-1, 0, 0, 0, 1, 0, 0, 0, 1]\n").is_err());
+
+        for (src, col) in [("[camera]\nzoom = nan\n", 8), ("[camera]\nzoom = inf\n", 8), ("[camera]\nzoom = 1e39\n", 8), ("[camera]\ntarget = [0, -inf, 0]\n", 14)] {
+            let e = parse_scene_file(src).err().unwrap();
+            assert_eq!((e.line, e.col), (2, col), "{}", src);
+            assert!(e.msg.starts_with("invalid number"), "{}", e.msg);
+        }
+    }
+
+    #[test]
+    fn bad_value_reports_its_position() {
+        let e = parse_scene_file("[camera]\nyaw = 0\npitch = \"up\"\n").err().unwrap();
+        assert_eq!((e.line, e.col), (3, 9));
+        assert!(e.msg.contains("`pitch` expects a number"), "{}", e.msg);
+
+        let e = parse_scene_file("[[body]]\nkind = \"rocky\"\n  rocky.c_ocean = \"#12345\"\n").err().unwrap();
+        assert_eq!((e.line, e.col), (3, 19));
+        assert_eq!(e.msg, "invalid color \"#12345\", expected \"#rrggbb\"");
+    }
+
+    #[test]
+    fn unknown_key_reports_its_position() {
+        let e = parse_scene_file("[background]\nstarz = 1\n").err().unwrap();
+        assert_eq!((e.line, e.col), (2, 1));
+        assert_eq!(e.msg, "unknown key `starz` in [background]");
+
+        let e = parse_scene_file("[[belt]]\nrin = 2\n    asteroid.lumps = 3\n").err().unwrap();
+        assert_eq!((e.line, e.col), (3, 5));
+        assert_eq!(e.msg, "unknown parameter `asteroid.lumps`");
+    }
+}
@@ -0,0 +1,253 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::math::Color;
+
+// Anything we can read 8-bit RGB triples out of: packed 0x00RRGGBB or float colors.
+pub trait Pixels {
+    fn len(&self) -> usize;
+    fn rgb8(&self, i: usize) -> [u8; 3];
+    fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+impl Pixels for [u32] {
+    fn len(&self) -> usize { <[u32]>::len(self) }
+    fn rgb8(&self, i: usize) -> [u8; 3] {
+        let p = self[i];
+        [(p >> 16) as u8, (p >> 8) as u8, p as u8]
+    }
+}
+
+impl Pixels for [Color] {
+    fn len(&self) -> usize { <[Color]>::len(self) }
+    fn rgb8(&self, i: usize) -> [u8; 3] {
+        let c = self[i].clamp01();
+        [(c.x * 255.0) as u8, (c.y * 255.0) as u8, (c.z * 255.0) as u8]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat { Ppm, Tga, Png }
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "ppm" => Some(Self::Ppm),
+            "tga" => Some(Self::Tga),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+}
+
+fn check_size<P: Pixels + ?Sized>(width: usize, height: usize, px: &P) -> io::Result<()> {
+    if px.len() < width * height {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "pixel buffer smaller than width * height"));
+    }
+    Ok(())
+}
+
+// Picks the format from the file extension.
+pub fn save<P: Pixels + ?Sized>(path: impl AsRef<Path>, width: usize, height: usize, px: &P) -> io::Result<()> {
+    let path = path.as_ref();
+    let format = ImageFormat::from_path(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unknown image extension (ppm, tga, png)"))?;
+    let mut w = BufWriter::new(File::create(path)?);
+    match format {
+        ImageFormat::Ppm => write_ppm(&mut w, width, height, px)?,
+        ImageFormat::Tga => write_tga(&mut w, width, height, px)?,
+        ImageFormat::Png => write_png(&mut w, width, height, px)?,
+    }
+    w.flush()
+}
+
+// Binary PPM (P6)
+pub fn write_ppm<W: Write, P: Pixels + ?Sized>(w: &mut W, width: usize, height: usize, px: &P) -> io::Result<()> {
+    check_size(width, height, px)?;
+    write!(w, "P6\n{} {}\n255\n", width, height)?;
+    let mut row = Vec::with_capacity(width * 3);
+    for y in 0..height {
+        row.clear();
+        for x in 0..width {
+            row.extend_from_slice(&px.rgb8(y * width + x));
+        }
+        w.write_all(&row)?;
+    }
+    Ok(())
+}
+
+// Uncompressed true-color TGA, 24 bpp, top-left origin
+pub fn write_tga<W: Write, P: Pixels + ?Sized>(w: &mut W, width: usize, height: usize, px: &P) -> io::Result<()> {
+    check_size(width, height, px)?;
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "TGA dimensions exceed 65535"));
+    }
+    let mut header = [0u8; 18];
+    header[2] = 2; // uncompressed true-color
+    header[12..14].copy_from_slice(&(width as u16).to_le_bytes());
+    header[14..16].copy_from_slice(&(height as u16).to_le_bytes());
+    header[16] = 24;
+    header[17] = 0x20; // origin top-left
+    w.write_all(&header)?;
+    let mut row = Vec::with_capacity(width * 3);
+    for y in 0..height {
+        row.clear();
+        for x in 0..width {
+            let [r, g, b] = px.rgb8(y * width + x);
+            row.extend_from_slice(&[b, g, r]);
+        }
+        w.write_all(&row)?;
+    }
+    Ok(())
+}
+
+// 8-bit RGB PNG. The zlib stream uses stored (uncompressed) deflate blocks.
+pub fn write_png<W: Write, P: Pixels + ?Sized>(w: &mut W, width: usize, height: usize, px: &P) -> io::Result<()> {
+    check_size(width, height, px)?;
+    if width > u32::MAX as usize || height > u32::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "PNG dimensions exceed u32"));
+    }
+    w.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'])?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // depth 8, RGB, deflate, filter 0, no interlace
+    write_chunk(w, b"IHDR", &ihdr)?;
+
+    // scanlines: filter byte 0 (None) + RGB
+    let mut raw = Vec::with_capacity(height * (1 + width * 3));
+    for y in 0..height {
+        raw.push(0);
+        for x in 0..width {
+            raw.extend_from_slice(&px.rgb8(y * width + x));
+        }
+    }
+    write_chunk(w, b"IDAT", &zlib_store(&raw))?;
+    write_chunk(w, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let crc = crc32_update(crc32_update(0xffff_ffff, kind), data) ^ 0xffff_ffff;
+    w.write_all(&crc.to_be_bytes())
+}
+
+// zlib container around stored deflate blocks (max 65535 bytes each)
+fn zlib_store(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xffff;
+    let blocks = data.len().div_ceil(MAX_BLOCK).max(1);
+    let mut out = Vec::with_capacity(2 + data.len() + blocks * 5 + 4);
+    out.extend_from_slice(&[0x78, 0x01]); // deflate, 32K window, no preset dict
+    let mut chunks = data.chunks(MAX_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+        out.push(last as u8); // BFINAL, BTYPE=00
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest block that cannot overflow u32 before the modulo
+    for chunk in data.chunks(5552) {
+        for &x in chunk {
+            a += x as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &x in data {
+        crc ^= x as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PX: [u32; 4] = [0x112233, 0x445566, 0x778899, 0xaabbcc];
+
+    fn crc32(data: &[u8]) -> u32 { crc32_update(0xffff_ffff, data) ^ 0xffff_ffff }
+
+    #[test]
+    fn checksums_match_reference_values() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(&[]), 1);
+    }
+
+    #[test]
+    fn ppm_is_header_then_rgb_rows() {
+        let mut out = Vec::new();
+        write_ppm(&mut out, 2, 2, &PX[..]).unwrap();
+        let mut want = b"P6\n2 2\n255\n".to_vec();
+        want.extend_from_slice(&[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc]);
+        assert_eq!(out, want);
+    }
+
+    #[test]
+    fn tga_is_top_left_bgr() {
+        let mut out = Vec::new();
+        write_tga(&mut out, 2, 2, &PX[..]).unwrap();
+        assert_eq!(out[..18], [0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, 0, 24, 0x20]);
+        assert_eq!(out[18..], [0x33, 0x22, 0x11, 0x66, 0x55, 0x44, 0x99, 0x88, 0x77, 0xcc, 0xbb, 0xaa]);
+    }
+
+    #[test]
+    fn png_chunks_and_zlib_stream_check_out() {
+        let mut out = Vec::new();
+        write_png(&mut out, 2, 2, &PX[..]).unwrap();
+        assert_eq!(out[..8], [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']);
+
+        // every chunk: length, type, data and the CRC of type + data
+        let mut chunks = Vec::new();
+        let mut at = 8;
+        while at < out.len() {
+            let len = u32::from_be_bytes(out[at..at + 4].try_into().unwrap()) as usize;
+            let body = &out[at + 4..at + 8 + len];
+            let crc = u32::from_be_bytes(out[at + 8 + len..at + 12 + len].try_into().unwrap());
+            assert_eq!(crc, crc32(body), "chunk {:?}", std::str::from_utf8(&body[..4]));
+            chunks.push((&body[..4], &body[4..]));
+            at += 12 + len;
+        }
+        assert_eq!(at, out.len());
+        let kinds: Vec<&[u8]> = chunks.iter().map(|c| c.0).collect();
+        assert_eq!(kinds, [&b"IHDR"[..], b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+
+        // zlib: header, one final stored block with LEN/NLEN, the filter-0 rows, then adler32
+        let raw = [0, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc];
+        let z = chunks[1].1;
+        assert_eq!(z[..2], [0x78, 0x01]);
+        assert_eq!(u16::from_be_bytes([z[0], z[1]]) % 31, 0);
+        assert_eq!(z[2..7], [1, 14, 0, !14u8, 0xff]);
+        assert_eq!(z[7..7 + raw.len()], raw);
+        assert_eq!(z[7 + raw.len()..], adler32(&raw).to_be_bytes());
+        assert!(chunks[2].1.is_empty());
+    }
+}
@@ -1,7 +1,17 @@
+pub mod asteroids;
+pub mod blackhole;
+pub mod format;
+pub mod image;
 pub mod math;
 pub mod noise;
+pub mod reload;
+pub mod render;
+pub mod scene;
 pub mod shader;
 
+pub use asteroids::{Asteroids, Belt, Rock};
 pub use math::{Color, Vec3, PI};
-pub use noise::{fbm3, hash31};
+pub use noise::{craters, fbm, fbm3, hash31, worley3, worley_point, Craters, Metric, NoiseBasis, Worley};
+pub use render::{Camera, Renderer};
+pub use scene::{Light, Scene, SceneBody, Transform};
 pub use shader::*;
\ No newline at end of file
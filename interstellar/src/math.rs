@@ -13,6 +13,53 @@ impl Vec3 {
     pub fn clamp01(self) -> Self { Self::new(self.x.clamp(0.0,1.0), self.y.clamp(0.0,1.0), self.z.clamp(0.0,1.0)) }
     pub fn mix(self, b: Self, k: f32) -> Self { self*(1.0-k) + b*k }
     pub fn mul_scalar(self, s: f32) -> Self { Self::new(self.x*s, self.y*s, self.z*s) }
+    pub fn cross(self, o: Self) -> Self { Self::new(self.y*o.z - self.z*o.y, self.z*o.x - self.x*o.z, self.x*o.y - self.y*o.x) }
+}
+
+// 3x3 matrix stored as rows; used for body orientations
+#[derive(Copy, Clone, Debug)]
+pub struct Mat3 { pub rows: [Vec3; 3] }
+
+impl Default for Mat3 { fn default() -> Self { Self::IDENTITY } }
+
+impl Mat3 {
+    pub const IDENTITY: Self = Self { rows: [Vec3::new(1.0,0.0,0.0), Vec3::new(0.0,1.0,0.0), Vec3::new(0.0,0.0,1.0)] };
+    pub fn rotation_x(a: f32) -> Self {
+        let (s, c) = a.sin_cos();
+        Self { rows: [vec3(1.0,0.0,0.0), vec3(0.0,c,-s), vec3(0.0,s,c)] }
+    }
+    pub fn rotation_y(a: f32) -> Self {
+        let (s, c) = a.sin_cos();
+        Self { rows: [vec3(c,0.0,s), vec3(0.0,1.0,0.0), vec3(-s,0.0,c)] }
+    }
+    pub fn rotation_z(a: f32) -> Self {
+        let (s, c) = a.sin_cos();
+        Self { rows: [vec3(c,-s,0.0), vec3(s,c,0.0), vec3(0.0,0.0,1.0)] }
+    }
+    // Rodrigues: rotation by `a` radians about the unit vector `k`
+    pub fn rotation_axis(k: Vec3, a: f32) -> Self {
+        let (s, c) = a.sin_cos();
+        let t = 1.0 - c;
+        Self { rows: [
+            vec3(t*k.x*k.x + c,     t*k.x*k.y - s*k.z, t*k.x*k.z + s*k.y),
+            vec3(t*k.x*k.y + s*k.z, t*k.y*k.y + c,     t*k.y*k.z - s*k.x),
+            vec3(t*k.x*k.z - s*k.y, t*k.y*k.z + s*k.x, t*k.z*k.z + c),
+        ] }
+    }
+    pub fn transpose(self) -> Self {
+        let [a, b, c] = self.rows;
+        Self { rows: [vec3(a.x,b.x,c.x), vec3(a.y,b.y,c.y), vec3(a.z,b.z,c.z)] }
+    }
+    pub fn mul_vec(self, v: Vec3) -> Vec3 { vec3(self.rows[0].dot(v), self.rows[1].dot(v), self.rows[2].dot(v)) }
+}
+
+impl Mul for Mat3 {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self {
+        let t = o.transpose();
+        let r = |row: Vec3| vec3(row.dot(t.rows[0]), row.dot(t.rows[1]), row.dot(t.rows[2]));
+        Self { rows: [r(self.rows[0]), r(self.rows[1]), r(self.rows[2])] }
+    }
 }
 
 impl Add for Vec3 { type Output = Self; fn add(self, o: Self) -> Self { Self::new(self.x+o.x, self.y+o.y, self.z+o.z) } }
@@ -36,6 +83,12 @@ impl Mul<Vec3> for f32 {
 
 #[inline] pub fn saturate(x: f32) -> f32 { x.clamp(0.0, 1.0) }
 #[inline] pub fn mix(a: f32, b: f32, k: f32) -> f32 { a*(1.0-k) + b*k }
+#[inline] pub fn smoothstep(e0: f32, e1: f32, x: f32) -> f32 { let t = saturate((x-e0)/(e1-e0)); t*t*(3.0-2.0*t) }
+// d/dx of smoothstep(e0, e1, x)
+#[inline] pub fn smoothstep_d(e0: f32, e1: f32, x: f32) -> f32 {
+    let t = (x-e0)/(e1-e0);
+    if t <= 0.0 || t >= 1.0 { 0.0 } else { 6.0*t*(1.0-t)/(e1-e0) }
+}
 
 #[inline] pub fn vec3(x: f32, y: f32, z: f32) -> Vec3 { Vec3::new(x,y,z) }
 #[inline] pub fn rgb(r: f32, g: f32, b: f32) -> Color { vec3(r,g,b) }
@@ -58,4 +111,45 @@ pub fn lat_lon_from_normal(n: Vec3) -> (f32, f32) {
 
 pub fn rim_term(n: Vec3, v: Vec3, power: f32) -> f32 {
     (1.0 - n.dot(-v).clamp(-1.0, 1.0)).powf(power)
-}
\ No newline at end of file
+}
+
+// Color of a blackbody at `kelvin` in linear sRGB, scaled so the brightest channel is 1.
+// Planck's law integrated against the CIE 1931 color matching functions (multi-lobe Gaussian
+// fit of Wyman, Sloan & Shirley 2013), precomputed on a log-spaced table and interpolated.
+pub fn blackbody(kelvin: f32) -> Color {
+    const T_MIN: f32 = 500.0;
+    const T_MAX: f32 = 50000.0;
+    const N: usize = 256;
+    static TABLE: std::sync::OnceLock<Vec<Color>> = std::sync::OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        (0..N).map(|i| blackbody_exact(T_MIN * (T_MAX / T_MIN).powf(i as f32 / (N - 1) as f32))).collect()
+    });
+    let u = (kelvin.clamp(T_MIN, T_MAX) / T_MIN).ln() / (T_MAX / T_MIN).ln() * (N - 1) as f32;
+    let i = (u as usize).min(N - 2);
+    table[i].mix(table[i + 1], u - i as f32)
+}
+
+fn blackbody_exact(kelvin: f32) -> Color {
+    // lóbulo gaussiano con anchos distintos a cada lado del pico
+    let lobe = |l: f32, mu: f32, s1: f32, s2: f32| {
+        let d = (l - mu) / if l < mu { s1 } else { s2 };
+        (-0.5 * d * d).exp()
+    };
+    let mut xyz = vec3(0.0, 0.0, 0.0);
+    for i in 0..=80 {
+        let l = 380.0 + 5.0 * i as f32; // nm
+        // Planck sin constantes (se normaliza al final); c2 = hc/k en nm·K
+        let planck = 1.0 / ((l / 500.0).powi(5) * ((1.4388e7 / (l * kelvin)).exp() - 1.0));
+        let x = 1.056 * lobe(l, 599.8, 37.9, 31.0) + 0.362 * lobe(l, 442.0, 16.0, 26.7) - 0.065 * lobe(l, 501.1, 20.4, 26.2);
+        let y = 0.821 * lobe(l, 568.8, 46.9, 40.5) + 0.286 * lobe(l, 530.9, 16.3, 31.1);
+        let z = 1.217 * lobe(l, 437.0, 11.8, 36.0) + 0.681 * lobe(l, 459.0, 26.0, 13.8);
+        xyz += vec3(x, y, z) * planck;
+    }
+    // XYZ -> sRGB lineal (D65)
+    let c = vec3(
+        (3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z).max(0.0),
+        (-0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z).max(0.0),
+        (0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z).max(0.0),
+    );
+    c / c.x.max(c.y).max(c.z).max(1e-12)
+}
@@ -1,28 +1,55 @@
-use crate::math::{Vec3, vec3, mix};
+use crate::math::{Vec3, vec3, mix, smoothstep};
 
-// simple hash/murmur-ish
-pub fn hash31(p: [f32;3]) -> f32 {
-    let mut x = p[0]*127.1 + p[1]*311.7 + p[2]*74.7;
-    x = (x.sin()*43758.5453).fract();
-    x
+// Integer lattice hash keyed by an explicit seed (xxHash64-style mixing).
+// Only integer ops: identical output on every platform and libm, at any coordinate.
+const P1: u64 = 0x9e37_79b1_85eb_ca87;
+const P2: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const P3: u64 = 0x1656_67b1_9e37_79f9;
+
+fn avalanche(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(P2);
+    h ^= h >> 29;
+    h = h.wrapping_mul(P3);
+    h ^ (h >> 32)
+}
+
+pub fn hash_lattice(coords: &[i64], seed: u64) -> u64 {
+    let mut h = seed.wrapping_add(P3);
+    for &c in coords {
+        h = (h ^ (c as u64).wrapping_mul(P1)).rotate_left(31).wrapping_mul(P2);
+    }
+    avalanche(h)
+}
+
+// top 24 bits -> [0,1), exact in f32
+#[inline]
+pub fn unit_f32(h: u64) -> f32 { (h >> 40) as f32 / (1u64 << 24) as f32 }
+
+pub fn hash31(p: [i64;3], seed: u64) -> f32 {
+    unit_f32(hash_lattice(&p, seed))
 }
 
 fn floor3(p: Vec3) -> Vec3 { vec3(p.x.floor(), p.y.floor(), p.z.floor()) }
-fn fract3(p: Vec3) -> Vec3 { vec3(p.x.fract(), p.y.fract(), p.z.fract()) }
+fn cell3(i: Vec3) -> [i64;3] { [i.x as i64, i.y as i64, i.z as i64] }
+fn offset3(c: [i64;3], dx: i64, dy: i64, dz: i64) -> [i64;3] {
+    [c[0].wrapping_add(dx), c[1].wrapping_add(dy), c[2].wrapping_add(dz)]
+}
 
-pub fn value_noise3(p: Vec3) -> f32 {
+pub fn value_noise3(p: Vec3, seed: u64) -> f32 {
     let i = floor3(p);
-    let f = fract3(p);
+    let f = p - i;
+    let c = cell3(i);
     let u = vec3( f.x*f.x*(3.0-2.0*f.x), f.y*f.y*(3.0-2.0*f.y), f.z*f.z*(3.0-2.0*f.z) );
 
-    let h000 = hash31([i.x,   i.y,   i.z  ]);
-    let h100 = hash31([i.x+1.0,i.y,  i.z  ]);
-    let h010 = hash31([i.x,   i.y+1.0,i.z  ]);
-    let h110 = hash31([i.x+1.0,i.y+1.0,i.z ]);
-    let h001 = hash31([i.x,   i.y,   i.z+1.0]);
-    let h101 = hash31([i.x+1.0,i.y,  i.z+1.0]);
-    let h011 = hash31([i.x,   i.y+1.0,i.z+1.0]);
-    let h111 = hash31([i.x+1.0,i.y+1.0,i.z+1.0]);
+    let h000 = hash31(offset3(c, 0, 0, 0), seed);
+    let h100 = hash31(offset3(c, 1, 0, 0), seed);
+    let h010 = hash31(offset3(c, 0, 1, 0), seed);
+    let h110 = hash31(offset3(c, 1, 1, 0), seed);
+    let h001 = hash31(offset3(c, 0, 0, 1), seed);
+    let h101 = hash31(offset3(c, 1, 0, 1), seed);
+    let h011 = hash31(offset3(c, 0, 1, 1), seed);
+    let h111 = hash31(offset3(c, 1, 1, 1), seed);
 
     let x00 = mix(h000, h100, u.x);
     let x10 = mix(h010, h110, u.x);
@@ -35,13 +62,493 @@ pub fn value_noise3(p: Vec3) -> f32 {
     mix(y0, y1, u.z)
 }
 
-pub fn fbm3(mut p: Vec3, octaves: i32, lacunarity: f32, gain: f32) -> f32 {
+// value_noise3 and its gradient with respect to p
+pub fn value_noise3_grad(p: Vec3, seed: u64) -> (f32, Vec3) {
+    let i = floor3(p);
+    let f = p - i;
+    let c = cell3(i);
+    let u = vec3( f.x*f.x*(3.0-2.0*f.x), f.y*f.y*(3.0-2.0*f.y), f.z*f.z*(3.0-2.0*f.z) );
+    let du = vec3( 6.0*f.x*(1.0-f.x), 6.0*f.y*(1.0-f.y), 6.0*f.z*(1.0-f.z) );
+
+    let h = |dx, dy, dz| hash31(offset3(c, dx, dy, dz), seed);
+    let (va, vb, vc, vd) = (h(0, 0, 0), h(1, 0, 0), h(0, 1, 0), h(1, 1, 0));
+    let (ve, vf, vg, vh) = (h(0, 0, 1), h(1, 0, 1), h(0, 1, 1), h(1, 1, 1));
+
+    // the trilinear mix written as a polynomial in u, as in gradient_noise3
+    let k0 = va;
+    let k1 = vb - va;
+    let k2 = vc - va;
+    let k3 = ve - va;
+    let k4 = va - vb - vc + vd;
+    let k5 = va - vc - ve + vg;
+    let k6 = va - vb - ve + vf;
+    let k7 = -va + vb + vc - vd + ve - vf - vg + vh;
+
+    let v = k0 + k1*u.x + k2*u.y + k3*u.z + k4*u.x*u.y + k5*u.y*u.z + k6*u.z*u.x + k7*u.x*u.y*u.z;
+    let d = vec3(
+        du.x * (k1 + k4*u.y + k6*u.z + k7*u.y*u.z),
+        du.y * (k2 + k5*u.z + k4*u.x + k7*u.z*u.x),
+        du.z * (k3 + k6*u.x + k5*u.y + k7*u.x*u.y),
+    );
+    (v, d)
+}
+
+// each octave gets its own seed so the layers are decorrelated
+pub fn fbm3(mut p: Vec3, seed: u64, octaves: i32, lacunarity: f32, gain: f32) -> f32 {
     let mut amp = 0.5;
     let mut sum = 0.0;
-    for _ in 0..octaves {
-        sum += amp * value_noise3(p);
+    for o in 0..octaves {
+        sum += amp * value_noise3(p, seed.wrapping_add(o as u64));
         p = Vec3::new(p.x*lacunarity, p.y*lacunarity, p.z*lacunarity);
         amp *= gain;
     }
     sum
-}
\ No newline at end of file
+}
+
+// --- Gradient (Perlin) and simplex noise -----------------------------------
+// Values are roughly in [-1,1]; the second member of the tuple is the analytic gradient.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoiseBasis { Value, Gradient, Simplex }
+
+impl NoiseBasis {
+    pub const ALL: [NoiseBasis; 3] = [NoiseBasis::Value, NoiseBasis::Gradient, NoiseBasis::Simplex];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NoiseBasis::Value    => "value",
+            NoiseBasis::Gradient => "gradient",
+            NoiseBasis::Simplex  => "simplex",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|b| b.name() == name)
+    }
+}
+
+// one of the 12 cube-edge directions
+fn grad3(c: [i64;3], seed: u64) -> Vec3 {
+    const G: [[f32;3]; 12] = [
+        [1.0,1.0,0.0], [-1.0,1.0,0.0], [1.0,-1.0,0.0], [-1.0,-1.0,0.0],
+        [1.0,0.0,1.0], [-1.0,0.0,1.0], [1.0,0.0,-1.0], [-1.0,0.0,-1.0],
+        [0.0,1.0,1.0], [0.0,-1.0,1.0], [0.0,1.0,-1.0], [0.0,-1.0,-1.0],
+    ];
+    let k = (hash_lattice(&c, seed) % 12) as usize;
+    vec3(G[k][0], G[k][1], G[k][2])
+}
+
+// one of the 32 tesseract-edge directions (one zero component, the rest ±1)
+fn grad4(c: [i64;4], seed: u64) -> [f32;4] {
+    let k = (hash_lattice(&c, seed) % 32) as usize;
+    let zero = k / 8;
+    let mut g = [0.0; 4];
+    let mut bit = 0;
+    for (j, gj) in g.iter_mut().enumerate() {
+        if j == zero { continue; }
+        *gj = if (k >> bit) & 1 == 0 { 1.0 } else { -1.0 };
+        bit += 1;
+    }
+    g
+}
+
+pub fn gradient_noise3(p: Vec3, seed: u64) -> (f32, Vec3) {
+    let i = floor3(p);
+    let f = p - i;
+    let c = cell3(i);
+
+    // quintic fade and its derivative
+    let fade = |t: f32| t*t*t*(t*(t*6.0-15.0)+10.0);
+    let dfade = |t: f32| 30.0*t*t*(t*(t-2.0)+1.0);
+    let u = vec3(fade(f.x), fade(f.y), fade(f.z));
+    let du = vec3(dfade(f.x), dfade(f.y), dfade(f.z));
+
+    let corner = |o: Vec3| { let g = grad3(offset3(c, o.x as i64, o.y as i64, o.z as i64), seed); (g, g.dot(f - o)) };
+    let (ga, va) = corner(vec3(0.0,0.0,0.0));
+    let (gb, vb) = corner(vec3(1.0,0.0,0.0));
+    let (gc, vc) = corner(vec3(0.0,1.0,0.0));
+    let (gd, vd) = corner(vec3(1.0,1.0,0.0));
+    let (ge, ve) = corner(vec3(0.0,0.0,1.0));
+    let (gf, vf) = corner(vec3(1.0,0.0,1.0));
+    let (gg, vg) = corner(vec3(0.0,1.0,1.0));
+    let (gh, vh) = corner(vec3(1.0,1.0,1.0));
+
+    let k0 = va;
+    let k1 = vb - va;
+    let k2 = vc - va;
+    let k3 = ve - va;
+    let k4 = va - vb - vc + vd;
+    let k5 = va - vc - ve + vg;
+    let k6 = va - vb - ve + vf;
+    let k7 = -va + vb + vc - vd + ve - vf - vg + vh;
+
+    let v = k0 + k1*u.x + k2*u.y + k3*u.z + k4*u.x*u.y + k5*u.y*u.z + k6*u.z*u.x + k7*u.x*u.y*u.z;
+
+    let g = ga + (gb-ga)*u.x + (gc-ga)*u.y + (ge-ga)*u.z
+        + (ga-gb-gc+gd)*(u.x*u.y) + (ga-gc-ge+gg)*(u.y*u.z) + (ga-gb-ge+gf)*(u.z*u.x)
+        + (-ga+gb+gc-gd+ge-gf-gg+gh)*(u.x*u.y*u.z);
+    let d = vec3(
+        du.x * (k1 + k4*u.y + k6*u.z + k7*u.y*u.z),
+        du.y * (k2 + k5*u.z + k4*u.x + k7*u.z*u.x),
+        du.z * (k3 + k6*u.x + k5*u.y + k7*u.x*u.y),
+    );
+    (v, g + d)
+}
+
+pub fn simplex3(p: Vec3, seed: u64) -> (f32, Vec3) {
+    const F3: f32 = 1.0/3.0;
+    const G3: f32 = 1.0/6.0;
+
+    // celda simplex (espacio sesgado) y primera esquina
+    let s = (p.x + p.y + p.z) * F3;
+    let i = floor3(p + vec3(s, s, s));
+    let t = (i.x + i.y + i.z) * G3;
+    let x0 = p - (i - vec3(t, t, t));
+    let c = cell3(i);
+
+    // orden de las coordenadas -> las otras dos esquinas
+    let (i1, i2) = if x0.x >= x0.y {
+        if x0.y >= x0.z      { (vec3(1.0,0.0,0.0), vec3(1.0,1.0,0.0)) }
+        else if x0.x >= x0.z { (vec3(1.0,0.0,0.0), vec3(1.0,0.0,1.0)) }
+        else                 { (vec3(0.0,0.0,1.0), vec3(1.0,0.0,1.0)) }
+    } else if x0.y < x0.z    { (vec3(0.0,0.0,1.0), vec3(0.0,1.0,1.0)) }
+    else if x0.x < x0.z      { (vec3(0.0,1.0,0.0), vec3(0.0,1.0,1.0)) }
+    else                     { (vec3(0.0,1.0,0.0), vec3(1.0,1.0,0.0)) };
+
+    let corners = [
+        (vec3(0.0,0.0,0.0), x0),
+        (i1, x0 - i1 + vec3(G3, G3, G3)),
+        (i2, x0 - i2 + vec3(2.0*G3, 2.0*G3, 2.0*G3)),
+        (vec3(1.0,1.0,1.0), x0 - vec3(1.0,1.0,1.0) + vec3(3.0*G3, 3.0*G3, 3.0*G3)),
+    ];
+
+    let mut v = 0.0;
+    let mut d = vec3(0.0, 0.0, 0.0);
+    for (o, x) in corners {
+        let t = 0.5 - x.dot(x);
+        if t <= 0.0 { continue; }
+        let g = grad3(offset3(c, o.x as i64, o.y as i64, o.z as i64), seed);
+        let gx = g.dot(x);
+        let t2 = t*t;
+        let t4 = t2*t2;
+        v += t4 * gx;
+        d += g*t4 - x*(8.0*t2*t*gx);
+    }
+    (75.0*v, d*75.0)
+}
+
+pub fn simplex4(p: [f32;4], seed: u64) -> (f32, [f32;4]) {
+    let f4 = (5.0f32.sqrt() - 1.0) / 4.0;
+    let g4 = (5.0 - 5.0f32.sqrt()) / 20.0;
+
+    let s = (p[0] + p[1] + p[2] + p[3]) * f4;
+    let i = p.map(|c| (c + s).floor());
+    let t = (i[0] + i[1] + i[2] + i[3]) * g4;
+    let x0: [f32;4] = core::array::from_fn(|k| p[k] - (i[k] - t));
+
+    // rango de cada coordenada decide el recorrido por el símplex
+    let mut rank = [0u8; 4];
+    for a in 0..4 {
+        for b in (a+1)..4 {
+            if x0[a] > x0[b] { rank[a] += 1; } else { rank[b] += 1; }
+        }
+    }
+
+    let mut v = 0.0;
+    let mut d = [0.0f32; 4];
+    for c in 0..5u8 {
+        // esquina c: coordenadas con rango >= 4-c avanzan una celda
+        let o: [f32;4] = core::array::from_fn(|k| if c > 0 && rank[k] >= 4 - c { 1.0 } else { 0.0 });
+        let x: [f32;4] = core::array::from_fn(|k| x0[k] - o[k] + c as f32 * g4);
+        let t = 0.5 - x.iter().map(|a| a*a).sum::<f32>();
+        if t <= 0.0 { continue; }
+        let g = grad4(core::array::from_fn(|k| (i[k] as i64).wrapping_add(o[k] as i64)), seed);
+        let gx: f32 = (0..4).map(|k| g[k]*x[k]).sum();
+        let t2 = t*t;
+        let t4 = t2*t2;
+        v += t4 * gx;
+        for k in 0..4 {
+            d[k] += g[k]*t4 - x[k]*(8.0*t2*t*gx);
+        }
+    }
+    (62.0*v, d.map(|a| a*62.0))
+}
+
+// Single octave of the chosen basis, remapped to [0,1] like value_noise3.
+pub fn noise3(basis: NoiseBasis, p: Vec3, seed: u64) -> f32 {
+    match basis {
+        NoiseBasis::Value    => value_noise3(p, seed),
+        NoiseBasis::Gradient => (0.5 + 0.5*gradient_noise3(p, seed).0).clamp(0.0, 1.0),
+        NoiseBasis::Simplex  => (0.5 + 0.5*simplex3(p, seed).0).clamp(0.0, 1.0),
+    }
+}
+
+// noise3 and its gradient with respect to p (zero where the remap clamps).
+pub fn noise3_grad(basis: NoiseBasis, p: Vec3, seed: u64) -> (f32, Vec3) {
+    let (v, g) = match basis {
+        NoiseBasis::Value    => return value_noise3_grad(p, seed),
+        NoiseBasis::Gradient => gradient_noise3(p, seed),
+        NoiseBasis::Simplex  => simplex3(p, seed),
+    };
+    let v = 0.5 + 0.5*v;
+    if (0.0..=1.0).contains(&v) { (v, g * 0.5) } else { (v.clamp(0.0, 1.0), vec3(0.0, 0.0, 0.0)) }
+}
+
+// fbm3 over any basis; with NoiseBasis::Value it matches fbm3 exactly.
+pub fn fbm(basis: NoiseBasis, mut p: Vec3, seed: u64, octaves: i32, lacunarity: f32, gain: f32) -> f32 {
+    let mut amp = 0.5;
+    let mut sum = 0.0;
+    for o in 0..octaves {
+        sum += amp * noise3(basis, p, seed.wrapping_add(o as u64));
+        p = p * lacunarity;
+        amp *= gain;
+    }
+    sum
+}
+
+// fbm and its gradient with respect to p.
+pub fn fbm_grad(basis: NoiseBasis, mut p: Vec3, seed: u64, octaves: i32, lacunarity: f32, gain: f32) -> (f32, Vec3) {
+    let mut amp = 0.5;
+    let mut freq = 1.0;
+    let mut sum = 0.0;
+    let mut grad = vec3(0.0, 0.0, 0.0);
+    for o in 0..octaves {
+        let (v, g) = noise3_grad(basis, p, seed.wrapping_add(o as u64));
+        sum += amp * v;
+        grad += g * (amp * freq);
+        p = p * lacunarity;
+        freq *= lacunarity;
+        amp *= gain;
+    }
+    (sum, grad)
+}
+
+
+// --- Cellular (Worley) noise ------------------------------------------------
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Metric { Euclidean, Manhattan, Chebyshev }
+
+impl Metric {
+    pub const ALL: [Metric; 3] = [Metric::Euclidean, Metric::Manhattan, Metric::Chebyshev];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Euclidean => "euclidean",
+            Metric::Manhattan => "manhattan",
+            Metric::Chebyshev => "chebyshev",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.name() == name)
+    }
+
+    pub fn dist(self, d: Vec3) -> f32 {
+        match self {
+            Metric::Euclidean => d.length(),
+            Metric::Manhattan => d.x.abs() + d.y.abs() + d.z.abs(),
+            Metric::Chebyshev => d.x.abs().max(d.y.abs()).max(d.z.abs()),
+        }
+    }
+}
+
+// Distances to the nearest (f1) and second nearest (f2) feature points,
+// plus the lattice cells of both, the nearest one's hash id and that id as a number in [0,1).
+#[derive(Copy, Clone, Debug)]
+pub struct Worley {
+    pub f1: f32,
+    pub f2: f32,
+    pub cell: [i64;3],
+    pub cell2: [i64;3],
+    pub id: u64,
+    pub rand: f32,
+}
+
+impl Worley {
+    // ~0 on the borders between cells: crack networks, cell walls
+    pub fn edge(&self) -> f32 { self.f2 - self.f1 }
+}
+
+// feature point of a cell, relative to the cell corner
+fn feature3(c: [i64;3], seed: u64) -> Vec3 {
+    let h = hash_lattice(&c, seed);
+    // three 21-bit fields of one hash
+    let k = (1u64 << 21) as f32;
+    vec3((h & 0x1f_ffff) as f32 / k, ((h >> 21) & 0x1f_ffff) as f32 / k, ((h >> 42) & 0x1f_ffff) as f32 / k)
+}
+
+// Feature point of lattice `cell` (e.g. `Worley::cell`), in the same space as worley3's `p`.
+pub fn worley_point(cell: [i64;3], seed: u64) -> Vec3 {
+    vec3(cell[0] as f32, cell[1] as f32, cell[2] as f32) + feature3(cell, seed)
+}
+
+pub fn worley3(p: Vec3, seed: u64, metric: Metric) -> Worley {
+    let i = floor3(p);
+    let f = p - i;
+    let c = cell3(i);
+    let mut w = Worley { f1: f32::MAX, f2: f32::MAX, cell: c, cell2: c, id: 0, rand: 0.0 };
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let cell = offset3(c, dx, dy, dz);
+                let o = vec3(dx as f32, dy as f32, dz as f32);
+                let d = metric.dist(o + feature3(cell, seed) - f);
+                if d < w.f1 {
+                    w.f2 = w.f1;
+                    w.cell2 = w.cell;
+                    w.f1 = d;
+                    w.cell = cell;
+                } else if d < w.f2 {
+                    w.f2 = d;
+                    w.cell2 = cell;
+                }
+            }
+        }
+    }
+    w.id = hash_lattice(&w.cell, seed ^ P1);
+    w.rand = unit_f32(w.id);
+    w
+}
+
+// --- Crater fields -----------------------------------------------------------
+
+// Sum of the craters around `p`: relief (bowl below 0, rim and ejecta above), its gradient
+// with respect to `p`, and the brightness of fresh craters and their ray streaks.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Craters {
+    pub height: f32,
+    pub grad: Vec3,
+    pub rays: f32,
+}
+
+// Craters of all sizes: octave `o` has cells 2^-o as large, so crater diameters halve each
+// octave, and a cell holds a crater with probability `density * 2^(o(power - 2))`; that is a
+// cumulative count N(>D) ∝ D^-power (≈ 2 on the Moon). Depth and rim scale with the diameter.
+// Neighbouring cells are summed rather than only the nearest, so overlapping craters and
+// rays that leave their cell have no seams.
+pub fn craters(p: Vec3, seed: u64, octaves: u32, power: f32, density: f32, rim: f32) -> Craters {
+    let mut out = Craters::default();
+    let mut q = p;
+    let mut size = 1.0;
+    for o in 0..octaves.min(8) {
+        let s = seed.wrapping_add(o as u64 * 0x51);
+        let chance = density * 2f32.powf(o as f32 * (power - 2.0));
+        let i = floor3(q);
+        let c = cell3(i);
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let cell = offset3(c, dx, dy, dz);
+                    let h = hash_lattice(&cell, s ^ P2);
+                    if unit_f32(h) >= chance { continue; }
+                    // los jóvenes son pequeños: sus rayos llegan a muchos radios
+                    let fresh = unit_f32(h.rotate_left(48)) < 0.16;
+                    let radius = (0.15 + 0.3 * unit_f32(h.rotate_left(24))) * if fresh { 0.4 } else { 1.0 };
+                    let to = q - (i + vec3(dx as f32, dy as f32, dz as f32) + feature3(cell, s));
+                    let dist = to.length();
+                    let d = dist / radius;
+
+                    // cuenco parabólico, borde gaussiano y manto de eyecta que decae con d^-3
+                    let bowl = if d < 1.0 { d*d - 1.0 } else { 0.0 };
+                    let lip = (-((d - 1.0) / 0.25).powi(2)).exp();
+                    let blanket = if d > 1.0 { d.powi(-3) * 0.3 } else { 0.0 };
+                    out.height += size * radius * (0.8*bowl + rim*(lip + blanket));
+
+                    // derivada en d; como q = p·2^o y size = 2^-o, el gradiente en p es dh/dd · to/dist
+                    let dbowl = if d < 1.0 { 2.0*d } else { 0.0 };
+                    let dlip = lip * -32.0 * (d - 1.0);
+                    let dblanket = if d > 1.0 { -0.9 * d.powi(-4) } else { 0.0 };
+                    out.grad += to * ((0.8*dbowl + rim*(dlip + dblanket)) / dist.max(1e-6));
+
+                    // ~1 de cada 6 es joven: interior claro y rayos hasta una celda de distancia
+                    if fresh {
+                        let streak = smoothstep(0.55, 0.85, value_noise3((to / dist.max(1e-6)) * 14.0, h));
+                        let fade = (1.0 - dist).max(0.0).powi(2);
+                        let halo = (1.0 - (d - 0.9) * 2.0).clamp(0.0, 1.0);
+                        let ray = if d > 1.0 { streak * fade * 2.5 } else { 0.0 };
+                        out.rays += halo.max(ray);
+                    }
+                }
+            }
+        }
+        q = q * 2.0;
+        size *= 0.5;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pinned outputs: these must not change across platforms, libm versions or builds.
+    #[test]
+    fn hash_is_pinned() {
+        assert_eq!(hash_lattice(&[0, 0, 0], 0), 0x49fc_2763_24fa_d272);
+        assert_eq!(hash_lattice(&[1, 2, 3], 42), 0x72f1_f5e1_c14f_c00c);
+        assert_eq!(hash_lattice(&[-5, 1 << 40, 7], u64::MAX), 0x100a_5d14_1a22_1f3a);
+        assert_eq!(hash31([1, 2, 3], 42), 0.449_004_47);
+    }
+
+    #[test]
+    fn noise_is_pinned() {
+        let p = vec3(1.25, -3.5, 0.75);
+        assert_eq!(value_noise3(p, 7), 0.110_926_226);
+        assert_eq!(fbm3(p, 7, 5, 2.0, 0.5), 0.245_647_64);
+        assert_eq!(gradient_noise3(p, 7).0, -0.067_376_14);
+        assert_eq!(simplex3(p, 7).0, -0.358_581_45);
+        assert_eq!(simplex4([1.25, -3.5, 0.75, 2.0], 7).0, 0.037_703_473);
+        let w = worley3(p, 7, Metric::Euclidean);
+        assert_eq!((w.f1, w.f2, w.cell), (0.517_557_7, 0.555_094_9, [1, -4, 1]));
+    }
+
+    #[test]
+    fn large_coordinates_keep_detail() {
+        // the old sin-fract hash collapsed to a few values this far out
+        assert_eq!(value_noise3(vec3(1.0e6 + 0.25, 3.5, -2.0e6 + 0.5), 7), 0.619_302_63);
+        let a = value_noise3(vec3(1.0e6, 0.5, 0.5), 1);
+        let b = value_noise3(vec3(1.0e6 + 1.0, 0.5, 0.5), 1);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn seed_changes_output() {
+        let p = vec3(0.3, 0.6, 0.9);
+        assert_ne!(value_noise3(p, 1), value_noise3(p, 2));
+        assert_eq!(value_noise3(p, 1), value_noise3(p, 1));
+    }
+
+    #[test]
+    fn crater_gradient_matches_finite_differences() {
+        let h = |p: Vec3| craters(p, 5, 3, 2.0, 0.8, 0.3).height;
+        for p in [vec3(0.3, 1.7, -2.2), vec3(4.1, 0.2, 0.9), vec3(-1.3, -0.6, 2.45)] {
+            let g = craters(p, 5, 3, 2.0, 0.8, 0.3).grad;
+            let e = 1e-3;
+            let fd = vec3(
+                h(p + vec3(e, 0.0, 0.0)) - h(p - vec3(e, 0.0, 0.0)),
+                h(p + vec3(0.0, e, 0.0)) - h(p - vec3(0.0, e, 0.0)),
+                h(p + vec3(0.0, 0.0, e)) - h(p - vec3(0.0, 0.0, e)),
+            ) / (2.0 * e);
+            assert!(fd.length() > 1e-3 && (g - fd).length() < 1e-2 * (1.0 + fd.length()), "{:?} vs {:?}", g, fd);
+        }
+    }
+
+    #[test]
+    fn fbm_gradient_matches_finite_differences() {
+        for basis in [NoiseBasis::Value, NoiseBasis::Gradient, NoiseBasis::Simplex] {
+            let h = |p: Vec3| fbm(basis, p, 9, 3, 2.0, 0.5);
+            for p in [vec3(0.3, 1.7, -2.2), vec3(4.1, 0.2, 0.9), vec3(-1.3, -0.6, 2.45)] {
+                let (v, g) = fbm_grad(basis, p, 9, 3, 2.0, 0.5);
+                assert!((v - h(p)).abs() < 1e-5);
+                let e = 1e-3;
+                let fd = vec3(
+                    h(p + vec3(e, 0.0, 0.0)) - h(p - vec3(e, 0.0, 0.0)),
+                    h(p + vec3(0.0, e, 0.0)) - h(p - vec3(0.0, e, 0.0)),
+                    h(p + vec3(0.0, 0.0, e)) - h(p - vec3(0.0, 0.0, e)),
+                ) / (2.0 * e);
+                assert!((g - fd).length() < 1e-2 * (1.0 + fd.length()), "{:?}: {:?} vs {:?}", basis, g, fd);
+            }
+        }
+    }
+}
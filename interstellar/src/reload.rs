@@ -0,0 +1,43 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::format::{self, LoadError, SceneFile};
+
+// Polls a scene/params file and re-parses it when it changes on disk.
+// The caller keeps its last good values when a reload fails.
+pub struct HotReload {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    error: Option<String>,
+}
+
+impl HotReload {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), stamp: None, error: None }
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    // Last reload error, cleared by the next successful reload.
+    pub fn error(&self) -> Option<&str> { self.error.as_deref() }
+
+    // Cheap enough to call every frame: one stat, and a parse only when mtime or size changed.
+    pub fn poll(&mut self) -> Option<Result<SceneFile, LoadError>> {
+        // a missing file is usually an editor mid-save; try again next frame
+        let meta = fs::metadata(&self.path).ok()?;
+        let stamp = (meta.modified().ok()?, meta.len());
+        if self.stamp == Some(stamp) {
+            return None;
+        }
+        self.stamp = Some(stamp);
+
+        let res = format::load_scene(&self.path);
+        self.error = match &res {
+            Ok(_) => None,
+            Err(LoadError::Parse(e)) => Some(format!("{}:{}", self.path.display(), e)),
+            Err(e) => Some(format!("{}: {}", self.path.display(), e)),
+        };
+        Some(res)
+    }
+}
@@ -0,0 +1,387 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::blackhole;
+use crate::math::*;
+use crate::scene::*;
+use crate::shader::*;
+
+// Orbit camera around `target`: yaw/pitch in radians, radius = distance,
+// zoom = apparent half-width (in screen units) of a unit sphere at the target.
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub radius: f32,
+    pub zoom: f32,
+    pub target: Vec3,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self { yaw: 0.0, pitch: 0.6, radius: 4.0, zoom: 1.4, target: vec3(0.0, 0.0, 0.0) }
+    }
+}
+
+impl Camera {
+    pub const MAX_PITCH: f32 = 1.3;
+
+    pub fn position(&self) -> Vec3 {
+        self.target + rotate_y(rotate_x(vec3(0.0, 0.0, self.radius), self.pitch), self.yaw)
+    }
+
+    pub fn orbit(&mut self, dyaw: f32, dpitch: f32) {
+        self.yaw += dyaw;
+        self.pitch = (self.pitch + dpitch).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.clamp(0.05, 5.0);
+    }
+
+    // (forward, right, up) in world space
+    pub fn basis(&self) -> (Vec3, Vec3, Vec3) {
+        let fwd = (self.target - self.position()).normalized();
+        let right = fwd.cross(vec3(0.0, 1.0, 0.0)).normalized();
+        let up = right.cross(fwd);
+        (fwd, right, up)
+    }
+
+    // Ray direction through screen point (sx, sy), sx in [-1,1] left→right, sy growing downwards.
+    pub fn ray_dir(&self, basis: (Vec3, Vec3, Vec3), sx: f32, sy: f32) -> Vec3 {
+        let (fwd, right, up) = basis;
+        let focal = self.zoom * self.radius;
+        (fwd*focal + right*sx - up*sy).normalized()
+    }
+}
+
+pub fn rotate_y(v: Vec3, yaw: f32) -> Vec3 {
+    let cy = yaw.cos();
+    let sy = yaw.sin();
+    vec3(v.x*cy + v.z*sy, v.y, -v.x*sy + v.z*cy)
+}
+
+pub fn rotate_x(v: Vec3, pitch: f32) -> Vec3 {
+    let cp = pitch.cos();
+    let sp = pitch.sin();
+    vec3(v.x, v.y*cp - v.z*sp, v.y*sp + v.z*cp)
+}
+
+// 0x00RRGGBB, the layout minifb expects
+#[inline]
+pub fn pack_rgb(c: Color) -> u32 {
+    let r = (c.x.clamp(0.0, 1.0) * 255.0) as u32;
+    let g = (c.y.clamp(0.0, 1.0) * 255.0) as u32;
+    let b = (c.z.clamp(0.0, 1.0) * 255.0) as u32;
+    (r << 16) | (g << 8) | b
+}
+
+// Offscreen renderer: fills a caller-owned `width * height` buffer, no window needed.
+// The frame is cut into `tile`×`tile` blocks shaded by `threads` workers; every pixel is
+// computed independently, so the output does not depend on the thread count.
+#[derive(Copy, Clone, Debug)]
+pub struct Renderer {
+    pub width: usize,
+    pub height: usize,
+    pub ringed: bool,
+    pub threads: usize,
+    pub tile: usize,
+    // debug view: only light emitted by the surfaces (city lights, stars, disks)
+    pub emissive_only: bool,
+}
+
+impl Renderer {
+    pub fn new(width: usize, height: usize) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Self { width, height, ringed: false, threads, tile: 32, emissive_only: false }
+    }
+
+    pub fn with_threads(self, threads: usize) -> Self {
+        Self { threads: threads.max(1), ..self }
+    }
+
+    // Single body at the origin, the classic viewer setup.
+    pub fn render(&self, cam: &Camera, params: &Params, body: Body, t: f32, out: &mut [u32]) {
+        let scene = Scene::single(body, *params, self.ringed, *cam);
+        self.render_scene(&scene, t, out);
+    }
+
+    pub fn render_scene(&self, scene: &Scene, t: f32, out: &mut [u32]) {
+        assert!(out.len() >= self.width * self.height, "framebuffer too small");
+        let view = View::new(scene, self.width, self.height, self.emissive_only);
+        if self.threads <= 1 {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    out[y * self.width + x] = view.pixel(x, y, t);
+                }
+            }
+            return;
+        }
+
+        let tile = self.tile.max(1);
+        let tiles_x = self.width.div_ceil(tile);
+        let tiles = tiles_x * self.height.div_ceil(tile);
+        let next = AtomicUsize::new(0);
+        let shared = Mutex::new(out);
+        thread::scope(|s| {
+            for _ in 0..self.threads.min(tiles) {
+                s.spawn(|| {
+                    let mut local = vec![0u32; tile * tile];
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= tiles { break; }
+                        let (x0, y0) = ((i % tiles_x) * tile, (i / tiles_x) * tile);
+                        let (w, h) = (tile.min(self.width - x0), tile.min(self.height - y0));
+                        for y in 0..h {
+                            for x in 0..w {
+                                local[y * w + x] = view.pixel(x0 + x, y0 + y, t);
+                            }
+                        }
+                        let mut out = shared.lock().unwrap_or_else(|e| e.into_inner());
+                        for y in 0..h {
+                            let row = (y0 + y) * self.width + x0;
+                            out[row..row + w].copy_from_slice(&local[y * w..(y + 1) * w]);
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
+
+// Per-frame camera data shared by all workers.
+struct View<'a> {
+    scene: &'a Scene,
+    ro: Vec3,
+    basis: (Vec3, Vec3, Vec3),
+    width: usize,
+    height: usize,
+    emissive_only: bool,
+}
+
+impl<'a> View<'a> {
+    fn new(scene: &'a Scene, width: usize, height: usize, emissive_only: bool) -> Self {
+        let cam = &scene.camera;
+        Self { scene, ro: cam.position(), basis: cam.basis(), width, height, emissive_only }
+    }
+
+    fn pixel(&self, x: usize, y: usize, t: f32) -> u32 {
+        let aspect = self.width as f32 / self.height as f32;
+        let sx = (x as f32 / self.width as f32) * 2.0 - 1.0;
+        let sy = ((y as f32 / self.height as f32) * 2.0 - 1.0) / aspect;
+        let rd = self.scene.camera.ray_dir(self.basis, sx, sy);
+        pack_rgb(trace(self.scene, self.ro, rd, Frame { t, emissive_only: self.emissive_only }, None, MAX_DEPTH))
+    }
+}
+
+// Nesting limit for continued rays: out of a black hole's influence sphere or through a ring.
+const MAX_DEPTH: u32 = 4;
+
+// Per-ray settings that stay the same through continued rays.
+#[derive(Copy, Clone)]
+struct Frame {
+    t: f32,
+    emissive_only: bool,
+}
+
+fn trace(scene: &Scene, ro: Vec3, rd: Vec3, frame: Frame, skip: Option<usize>, depth: u32) -> Color {
+    let t = frame.t;
+    let hit = scene.intersect(ro, rd, t);
+    let mut t_max = hit.map_or(f32::INFINITY, |h| h.t);
+
+    // El agujero negro más cercano cuya esfera de influencia alcanza el rayo antes que la
+    // superficie opaca; a partir de ahí el rayo se curva y se integra en su marco local.
+    let mut lens = None;
+    for (i, b) in scene.bodies.iter().enumerate() {
+        if b.body != Body::BlackHole || skip == Some(i) || depth == 0 { continue; }
+        let r_inf = blackhole::influence_radius(&b.params.disk) * b.radius;
+        let inside = (ro - b.center()).length() < r_inf;
+        let t_in = if inside { Some(0.0) } else { ray_sphere(ro, rd, b.center(), r_inf) };
+        if let Some(ti) = t_in.filter(|&ti| ti < t_max) {
+            t_max = ti;
+            lens = Some((i, ti));
+        }
+    }
+
+    let mut col = match (lens, hit) {
+        (None, Some(h)) => {
+            let (c, alpha) = match h.kind {
+                HitKind::Rock => {
+                    let rock = scene.asteroids.body(h.index, t);
+                    shade_hit(scene, &rock, Hit { kind: HitKind::Surface, ..h }, ro, rd, frame)
+                }
+                _ => shade_hit(scene, &scene.bodies[h.index], h, ro, rd, frame),
+            };
+            // anillo o gigante gaseoso translúcido: se compone sobre lo que hay detrás
+            if alpha < 1.0 && depth > 0 {
+                let t_on = match h.kind {
+                    // la esfera se cruza entera: seguir desde su lado de atrás (cuerda 2·(c - p)·d)
+                    HitKind::Surface => {
+                        let b = &scene.bodies[h.index];
+                        h.t + 2.0 * (b.center() - (ro + rd*h.t)).dot(rd)
+                    }
+                    _ => h.t,
+                };
+                c * alpha + trace(scene, ro + rd*(t_on + 1e-4), rd, frame, skip, depth - 1) * (1.0 - alpha)
+            } else {
+                c * alpha
+            }
+        }
+        // el rayo escapa: fondo de estrellas (lo lejano no se ve en la vista de emisión)
+        (None, None) if !frame.emissive_only => background(rd, &scene.background),
+        _ => vec3(0.0, 0.0, 0.0),
+    };
+
+    // Atmósferas: atenúan lo que hay detrás dentro de la capa y suman la luz dispersada
+    for b in &scene.bodies {
+        let density = atmosphere_density(b.body, &b.params);
+        if density <= 0.0 { continue; }
+        let tr = &b.transform;
+        let (l0, l1) = scene.light_dirs(b.center());
+        let o = tr.to_local(ro) / b.radius;
+        let d = tr.dir_to_local(rd);
+        if let Some((inscatter, trans)) = atmosphere(o, d, t_max / b.radius, tr.dir_to_local(l0), tr.dir_to_local(l1), &b.params.atmosphere, density) {
+            col = col * trans + if frame.emissive_only { vec3(0.0, 0.0, 0.0) } else { inscatter };
+        }
+    }
+
+    // Capas sin superficie: coronas estelares, comas y colas de cometas y discos emisivos
+    // delante de lo anterior
+    for b in &scene.bodies {
+        if b.body == Body::Comet {
+            let dist = match scene.key_lights().0 {
+                Light::Point(q) => (q - b.center()).length(),
+                Light::Directional(_) => b.params.comet.ref_distance,
+            };
+            // las colas apuntan en contra de la luz vista desde el núcleo, no desde la cámara
+            let (l0, _) = scene.light_dirs(b.center());
+            let ctx = ShadingCtx { l0: b.transform.dir_to_local(l0), ..local_ctx(scene, b, ro, rd, vec3(0.0, 1.0, 0.0), t) };
+            col += comet_glow(&ctx, t_max / b.radius, comet_activity(dist, &b.params.comet), &b.params.comet);
+            continue;
+        }
+        if b.body == Body::Star {
+            let tc = (b.center() - ro).dot(rd);
+            if tc > 0.0 && tc < t_max {
+                let pw = ro + rd*tc;
+                let ctx = local_ctx(scene, b, pw, rd, vec3(0.0, 1.0, 0.0), t);
+                if ctx.p.length() > 1.0 {
+                    col += shade_corona(&ctx, &b.params.star);
+                }
+            }
+            continue;
+        }
+        if b.body != Body::AccretionDisk { continue; }
+        let p = &b.params.disk;
+        if let Some((td, _)) = ray_ring(ro, rd, b, 0.0, p.rout + 1.0) {
+            if td < t_max {
+                let pw = ro + rd*td;
+                let ctx = local_ctx(scene, b, pw, rd, vec3(0.0, 1.0, 0.0), t);
+                let n = if ctx.v.y < 0.0 { -ctx.n } else { ctx.n };
+                col += shade(&ShadingCtx { n, ..ctx }, b.body, &b.params);
+            }
+        }
+    }
+
+    if let Some((i, ti)) = lens {
+        let b = &scene.bodies[i];
+        let tr = &b.transform;
+        let pw = ro + rd*ti;
+        let ctx = local_ctx(scene, b, pw, rd, vec3(0.0, 1.0, 0.0), t);
+        // otros cuerpos (o rocas) que asoman dentro de la esfera se buscan en cada paso de la
+        // geodésica; sin ninguno cerca no se paga esa búsqueda
+        let r_inf = blackhole::influence_radius(&b.params.disk) * b.radius;
+        let near = !scene.asteroids.rocks.is_empty() || scene.bodies.iter().enumerate().any(|(j, o)| {
+            let reach = if o.rings { o.params.ring.rout.max(1.0) } else { 1.0 } * o.radius;
+            j != i && !matches!(o.body, Body::BlackHole | Body::AccretionDisk) && (o.center() - b.center()).length() < r_inf + reach
+        });
+        let obstacle = |x: Vec3, d: Vec3, len: f32| {
+            let (pw, dw) = (tr.position + tr.dir_to_world(x) * b.radius, tr.dir_to_world(d));
+            scene.intersect(pw, dw, t).map(|h| h.t / b.radius).filter(|&s| s <= len)
+        };
+        let g = blackhole::trace_geodesic(ctx.p, -ctx.v, &b.params, ctx, near.then_some(obstacle));
+        col += g.color;
+        // lo que queda detrás: fuera de la esfera de influencia, o el cuerpo que cortó el camino
+        if let Some((x, v)) = g.exit {
+            let (pw, dw) = (tr.position + tr.dir_to_world(x) * b.radius, tr.dir_to_world(v));
+            col += trace(scene, pw, dw, frame, Some(i), depth - 1) * g.transmittance;
+        }
+    }
+    col
+}
+
+// Shading context in the body's local frame, scaled so the body has radius 1.
+fn local_ctx(scene: &Scene, b: &SceneBody, pw: Vec3, rd: Vec3, n_local: Vec3, t: f32) -> ShadingCtx {
+    let tr = &b.transform;
+    let (l0, l1) = scene.light_dirs(pw);
+    ShadingCtx {
+        p: tr.to_local(pw) / b.radius,
+        n: n_local,
+        v: tr.dir_to_local(-rd),
+        l0: tr.dir_to_local(l0),
+        l1: tr.dir_to_local(l1),
+        t,
+        seed: b.seed,
+    }
+}
+
+// (color, alpha): surfaces are opaque except a gas giant without rings, drawn at 70% as in
+// the original viewer; rings cover by their density.
+fn shade_hit(scene: &Scene, b: &SceneBody, hit: Hit, ro: Vec3, rd: Vec3, frame: Frame) -> (Color, f32) {
+    let pw = ro + rd*hit.t;
+    let t = frame.t;
+    match hit.kind {
+        HitKind::Surface | HitKind::Rock => {
+            let n = (b.transform.to_local(pw) / b.radius).normalized();
+            let ctx = local_ctx(scene, b, pw, rd, n, t);
+            if frame.emissive_only { return (emission(&ctx, b.body, &b.params), 1.0); }
+            if b.body == Body::Star { return (shade(&ctx, b.body, &b.params), 1.0); }
+            // la sombra solo oscurece la luz reflejada; el brillo propio (lava, ciudades) se suma después
+            let (lit, glow) = shade_parts(&ctx, b.body, &b.params);
+            let col = (lit.clamp01() * shadow(scene, pw, b.transform.dir_to_world(n), false) + glow).clamp01();
+            // transparencia de la gigante gaseosa sin anillos
+            let alpha = if b.body == Body::GasGiant && !b.rings { 0.7 } else { 1.0 };
+            (col, alpha)
+        }
+        HitKind::Ring => {
+            let ctx = local_ctx(scene, b, pw, rd, vec3(0.0, 1.0, 0.0), t);
+            let (col, alpha) = shade_ring(&ctx, &b.params.ring);
+            if frame.emissive_only { return (vec3(0.0, 0.0, 0.0), alpha); }
+            (col * shadow(scene, pw, b.transform.dir_to_world(ctx.n), true), alpha)
+        }
+    }
+}
+
+// Darkening from shadows of the two key lights, weighted like the shaders weigh them (0.7/0.3)
+// and only where the light actually falls; `two_sided` for thin surfaces such as rings.
+fn shadow(scene: &Scene, pw: Vec3, n: Vec3, two_sided: bool) -> f32 {
+    let (l0, l1) = scene.key_lights();
+    let blocked = |l: &Light| {
+        let nl = n.dot(l.dir_from(pw));
+        let lit = if two_sided { nl.abs() } else { saturate(nl) };
+        if lit <= 0.0 { 0.0 } else { (1.0 - scene.transmittance(pw, l)) * saturate(lit * 4.0) }
+    };
+    1.0 - 0.7*blocked(&l0) - 0.3*blocked(&l1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tiles, including ragged ones at the right and bottom edges, must cover every pixel and
+    // give the same image as a single thread.
+    #[test]
+    fn tiled_render_matches_single_thread() {
+        let (w, h) = (37, 23);
+        let scene = Scene::single(Body::Rocky, Params::default(), true, Camera::default());
+        let single = Renderer::new(w, h).with_threads(1);
+        let mut want = vec![u32::MAX; w * h];
+        single.render_scene(&scene, 0.5, &mut want);
+        assert!(want.iter().all(|&p| p != u32::MAX));
+        for tile in [1, 5, 7, 64] {
+            let mut got = vec![u32::MAX; w * h];
+            Renderer { tile, ..single.with_threads(4) }.render_scene(&scene, 0.5, &mut got);
+            assert!(got == want, "tile {tile}");
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use crate::asteroids::Asteroids;
+use crate::math::*;
+use crate::render::Camera;
+use crate::shader::*;
+
+// Placement of a body: world position plus orientation (local -> world).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Transform {
+    pub position: Vec3,
+    pub rotation: Mat3,
+}
+
+impl Transform {
+    pub fn at(position: Vec3) -> Self {
+        Self { position, rotation: Mat3::IDENTITY }
+    }
+
+    pub fn to_local(&self, p: Vec3) -> Vec3 { self.rotation.transpose().mul_vec(p - self.position) }
+    pub fn dir_to_local(&self, d: Vec3) -> Vec3 { self.rotation.transpose().mul_vec(d) }
+    pub fn dir_to_world(&self, d: Vec3) -> Vec3 { self.rotation.mul_vec(d) }
+}
+
+#[derive(Copy, Clone)]
+pub struct SceneBody {
+    pub body: Body,
+    pub transform: Transform,
+    pub radius: f32,
+    pub params: Params,
+    pub seed: u64,
+    pub rings: bool,
+}
+
+impl SceneBody {
+    pub fn new(body: Body, position: Vec3, radius: f32, params: Params) -> Self {
+        Self { body, transform: Transform::at(position), radius, params, seed: 0, rings: false }
+    }
+
+    pub fn center(&self) -> Vec3 { self.transform.position }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Light {
+    // direction towards the light
+    Directional(Vec3),
+    Point(Vec3),
+}
+
+impl Light {
+    // unit vector from `p` towards the light
+    pub fn dir_from(&self, p: Vec3) -> Vec3 {
+        match *self {
+            Light::Directional(d) => d.normalized(),
+            Light::Point(pos) => (pos - p).normalized(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HitKind { Surface, Ring, Rock }
+
+#[derive(Copy, Clone, Debug)]
+pub struct Hit {
+    pub t: f32,
+    // body, or rock in `asteroids` for HitKind::Rock
+    pub index: usize,
+    pub kind: HitKind,
+}
+
+#[derive(Clone)]
+pub struct Scene {
+    pub bodies: Vec<SceneBody>,
+    pub lights: Vec<Light>,
+    pub camera: Camera,
+    pub asteroids: Asteroids,
+    pub background: BackgroundParams,
+}
+
+impl Scene {
+    // The classic viewer setup: one body at the origin lit by the two "disk" lights.
+    pub fn single(body: Body, params: Params, rings: bool, camera: Camera) -> Self {
+        // holes and disks are measured in Schwarzschild radii, comets in nucleus radii; shrink
+        // them so the disk or the coma fits the frame
+        let radius = match body {
+            Body::BlackHole | Body::AccretionDisk => 0.06,
+            Body::Comet => 0.05,
+            _ => 1.0,
+        };
+        let mut b = SceneBody::new(body, vec3(0.0, 0.0, 0.0), radius, params);
+        b.rings = rings;
+        Self {
+            bodies: vec![b],
+            lights: vec![Light::Directional(vec3(0.0, 0.15, 1.0)), Light::Directional(vec3(0.0, 0.15, -1.0))],
+            camera,
+            asteroids: Asteroids::default(),
+            background: BackgroundParams::default(),
+        }
+    }
+
+    // (l0, l1) lights; falls back to the classic pair when lights are missing
+    pub fn key_lights(&self) -> (Light, Light) {
+        let l0 = self.lights.first().copied().unwrap_or(Light::Directional(vec3(0.0, 0.15, 1.0)));
+        let l1 = self.lights.get(1).copied().unwrap_or(match l0 {
+            Light::Directional(d) => Light::Directional(vec3(-d.x, d.y, -d.z)),
+            Light::Point(q) => Light::Point(vec3(-q.x, q.y, -q.z)),
+        });
+        (l0, l1)
+    }
+
+    // (l0, l1) light directions at `p`
+    pub fn light_dirs(&self, p: Vec3) -> (Vec3, Vec3) {
+        let (l0, l1) = self.key_lights();
+        (l0.dir_from(p), l1.dir_from(p))
+    }
+
+    // Fraction of `light` reaching `p`: solid bodies block it, rings let through 1 - density.
+    // Stars, black holes, disks, comets and asteroids (too small to matter) cast no shadow.
+    pub fn transmittance(&self, p: Vec3, light: &Light) -> f32 {
+        let dir = light.dir_from(p);
+        let dist = match *light {
+            Light::Directional(_) => f32::INFINITY,
+            Light::Point(q) => (q - p).length(),
+        };
+        let mut tr = 1.0;
+        for b in &self.bodies {
+            if matches!(b.body, Body::BlackHole | Body::AccretionDisk | Body::Star | Body::Comet | Body::Asteroid) {
+                continue;
+            }
+            if ray_sphere(p, dir, b.center(), b.radius).is_some_and(|t| t < dist) {
+                return 0.0;
+            }
+            if b.rings {
+                let rp = &b.params.ring;
+                if let Some((_, r)) = ray_ring(p, dir, b, rp.rin, rp.rout).filter(|h| h.0 > 1e-4 && h.0 < dist) {
+                    tr *= 1.0 - ring_density(r, rp, b.seed);
+                }
+            }
+        }
+        tr
+    }
+
+    // Nearest hit along the ray at `time` (asteroids spin): solid bodies, (translucent) ring
+    // planes and belt rocks. Black holes and accretion disks have no surface; the renderer
+    // layers them.
+    pub fn intersect(&self, ro: Vec3, rd: Vec3, time: f32) -> Option<Hit> {
+        let mut best: Option<Hit> = None;
+        let mut consider = |t: f32, index: usize, kind: HitKind| {
+            if best.is_none_or(|h| t < h.t) {
+                best = Some(Hit { t, index, kind });
+            }
+        };
+        for (i, b) in self.bodies.iter().enumerate() {
+            if matches!(b.body, Body::BlackHole | Body::AccretionDisk) {
+                continue;
+            }
+            let surface = match b.body {
+                Body::Comet | Body::Asteroid => ray_displaced(ro, rd, b),
+                _ => ray_sphere(ro, rd, b.center(), b.radius),
+            };
+            if let Some(t) = surface {
+                consider(t, i, HitKind::Surface);
+            }
+            if b.rings {
+                if let Some((t, _)) = ray_ring(ro, rd, b, b.params.ring.rin, b.params.ring.rout) {
+                    consider(t, i, HitKind::Ring);
+                }
+            }
+        }
+        let t_max = best.map_or(f32::INFINITY, |h| h.t);
+        if let Some((index, t)) = self.asteroids.intersect(ro, rd, t_max, time) {
+            best = Some(Hit { t, index, kind: HitKind::Rock });
+        }
+        best
+    }
+}
+
+// Nearest positive t where the ray enters the sphere (rd must be unit length).
+pub fn ray_sphere(ro: Vec3, rd: Vec3, center: Vec3, radius: f32) -> Option<f32> {
+    let oc = ro - center;
+    let b = oc.dot(rd);
+    let c = oc.dot(oc) - radius*radius;
+    let disc = b*b - c;
+    if disc < 0.0 { return None; }
+    let s = disc.sqrt();
+    let t0 = -b - s;
+    let t1 = -b + s;
+    if t0 > 1e-4 { Some(t0) } else if t1 > 1e-4 { Some(t1) } else { None }
+}
+
+// Comet nuclei and asteroids: `surface_radius` inside the body's bounding sphere.
+pub fn ray_displaced(ro: Vec3, rd: Vec3, b: &SceneBody) -> Option<f32> {
+    let o = b.transform.to_local(ro) / b.radius;
+    let d = b.transform.dir_to_local(rd);
+    march_displaced(o, d, |n| surface_radius(n, b.body, &b.params, b.seed), |n| surface_radius_coarse(n, b.body, &b.params, b.seed))
+        .map(|t| t * b.radius)
+}
+
+// Sphere tracing of the surface |p| = radius(p / |p|) in local units (bounding sphere of
+// radius 1). `coarse` gives a cheap (radius, max error) pair: while the ray is farther than that
+// error from it the step uses its bound, and only closer the full radius. The radial gap is not
+// a true distance, so a step may cross the surface; the crossing is then bracketed between two
+// exact gaps and refined by regula falsi. If the coarse steps run out inside the bounding
+// sphere the rest of the chord is marched on the exact gap with a small minimum step.
+pub fn march_displaced(o: Vec3, d: Vec3, radius: impl Fn(Vec3) -> f32, coarse: impl Fn(Vec3) -> (f32, f32)) -> Option<f32> {
+    let gap = |t: f32| {
+        let p = o + d*t;
+        let r = p.length();
+        r - radius(p / r.max(1e-6))
+    };
+    let t0 = if o.length() < 1.0 { 0.0 } else { ray_sphere(o, d, vec3(0.0, 0.0, 0.0), 1.0)? };
+    let leaving = |t: f32| { let p = o + d*t; p.length() > 1.001 && p.dot(d) > 0.0 };
+
+    // primer punto dentro: se acota entre él y un punto fuera con hueco exacto
+    let crossing = |outside: Option<(f32, f32)>, b: f32, gb: f32| -> f32 {
+        let (a, ga) = match outside {
+            Some(o) => o,
+            None => {
+                // sin punto previo (la superficie sobresale de la esfera o el origen está
+                // dentro): se retrocede hasta quedar fuera
+                let mut h = 0.02;
+                loop {
+                    let a = (b - h).max(0.0);
+                    let ga = gap(a);
+                    if ga > 0.0 { break (a, ga); }
+                    if a <= 0.0 { return 0.0; }
+                    h *= 2.0;
+                }
+            }
+        };
+        refine(gap, (a, ga), (b, gb))
+    };
+
+    let mut t = t0;
+    let mut outside: Option<(f32, f32)> = None; // último (t, hueco exacto) fuera de la superficie
+    let mut out_t = None;                       // último t fuera según la cota, sin hueco exacto
+    for _ in 0..64 {
+        if leaving(t) { return None; }
+        let p = o + d*t;
+        let r = p.length();
+        let (estimate, slack) = coarse(p / r.max(1e-6));
+        let bound = r - estimate - slack;
+        if bound >= slack.max(1e-3) {
+            out_t = Some(t);
+            t += bound * 0.9;
+            continue;
+        }
+        let g = gap(t);
+        if g <= 0.0 {
+            // la cota no guarda el hueco: se evalúa ahora si es el último punto fuera
+            let outside = match out_t {
+                Some(b) if outside.is_none_or(|(a, _)| b > a) => Some((b, gap(b))),
+                _ => outside,
+            };
+            return Some(crossing(outside, t, g));
+        }
+        outside = Some((t, g));
+        t += g * 0.9 + 2e-3;
+    }
+
+    // pasos agotados todavía dentro de la esfera: marcha fina sobre el hueco exacto
+    while !leaving(t) {
+        let g = gap(t);
+        if g <= 0.0 { return Some(crossing(outside, t, g)); }
+        outside = Some((t, g));
+        t += (g * 0.5).max(2e-3);
+    }
+    None
+}
+
+// Regula falsi (Illinois) between an exact gap outside (`a`) and one inside (`b`) the surface,
+// until the bracket is shorter than 5e-4 along the ray: a small gap alone is not enough on a
+// grazing ray, where it can hold for a long stretch of t.
+fn refine(gap: impl Fn(f32) -> f32, (mut a, mut ga): (f32, f32), (mut b, mut gb): (f32, f32)) -> f32 {
+    let mut side = 0;
+    for _ in 0..24 {
+        if b - a < 5e-4 { break; }
+        let m = (a + (b - a) * ga / (ga - gb)).clamp(a, b);
+        let gm = gap(m);
+        if gm == 0.0 { return m; }
+        if gm > 0.0 {
+            (a, ga) = (m, gm);
+            if side == 1 { gb *= 0.5; }
+            side = 1;
+        } else {
+            (b, gb) = (m, gm);
+            if side == -1 { ga *= 0.5; }
+            side = -1;
+        }
+    }
+    b
+}
+
+// Hit on the body's equatorial plane between rin and rout (in body radii).
+// Returns (t, radial distance in body radii).
+pub fn ray_ring(ro: Vec3, rd: Vec3, b: &SceneBody, rin: f32, rout: f32) -> Option<(f32, f32)> {
+    let o = b.transform.to_local(ro) / b.radius;
+    let d = b.transform.dir_to_local(rd);
+    if d.y.abs() <= 1e-5 { return None; }
+    let t = -o.y / d.y;
+    if t <= 0.0 { return None; }
+    let h = o + d * t;
+    let r = (h.x*h.x + h.z*h.z).sqrt();
+    if r < rin || r > rout { return None; }
+    Some((t * b.radius, r))
+}
@@ -1,8 +1,35 @@
 use crate::math::*;
 use crate::noise::*;
 
-#[derive(Copy, Clone)]
-pub enum Body { BlackHole, AccretionDisk, Rocky, GasGiant, Ice }
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Body { BlackHole, AccretionDisk, Rocky, GasGiant, Ice, Star, Lava, Desert, Moon, Comet, Asteroid }
+
+impl Body {
+    pub const ALL: [Body; 11] = [
+        Body::BlackHole, Body::AccretionDisk, Body::Rocky, Body::GasGiant, Body::Ice, Body::Star, Body::Lava, Body::Desert,
+        Body::Moon, Body::Comet, Body::Asteroid,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Body> {
+        Self::ALL.into_iter().find(|b| b.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Body::BlackHole     => "black_hole",
+            Body::AccretionDisk => "accretion_disk",
+            Body::Rocky         => "rocky",
+            Body::GasGiant      => "gas_giant",
+            Body::Ice           => "ice",
+            Body::Star          => "star",
+            Body::Lava          => "lava",
+            Body::Desert        => "desert",
+            Body::Moon          => "moon",
+            Body::Comet         => "comet",
+            Body::Asteroid      => "asteroid",
+        }
+    }
+}
 
 #[derive(Copy, Clone)]
 pub struct ShadingCtx {
@@ -12,13 +39,12 @@ pub struct ShadingCtx {
     pub l0: Vec3, // primary light dir (from point to light) for "disk"
     pub l1: Vec3, // secondary "curved" light
     pub t: f32,  // time
-    pub seed: f32,
+    pub seed: u64,
 }
 
 #[derive(Copy, Clone)]
 pub struct CommonParams {
     pub warm: Color,
-    pub cool: Color,
 }
 
 #[derive(Copy, Clone)]
@@ -26,7 +52,11 @@ pub struct DiskParams {
     pub rin: f32, pub rout: f32,
     pub bands_w: f32, pub bands_phi: f32,
     pub noise_freq: f32, pub noise_amp: f32,
+    // escala los corrimientos relativistas: 0 = sin efecto, 1 = físico
     pub beaming: f32,
+    // temperatura en rin (K) y mezcla paleta (0) / cuerpo negro (1)
+    pub temp_in: f32, pub temp_mix: f32,
+    pub basis: NoiseBasis,
     pub c1: Color, pub c2: Color, pub c3: Color,
 }
 
@@ -35,8 +65,19 @@ pub struct RockyParams {
     pub bioma_freq: f32,
     pub height_freq: f32,
     pub grad_amp: f32,
-    pub k_atm: f32,
-    pub c_land1: Color, pub c_land2: Color, pub c_ocean: Color,
+    pub k_atm: f32, // densidad de la atmósfera (0 = sin atmósfera)
+    pub basis: NoiseBasis,
+    // cráteres: 0 = sin cráteres
+    pub crater_freq: f32,
+    pub crater_depth: f32,
+    // océano: nivel del mar sobre h (0 = sin agua) y brillo del reflejo del sol
+    pub sea_level: f32,
+    pub shininess: f32,
+    // luces de ciudades en el lado nocturno (0 = apagadas)
+    pub city_lights: f32,
+    pub city_freq: f32,
+    pub c_land1: Color, pub c_land2: Color, pub c_ocean: Color, pub c_shallow: Color,
+    pub c_city: Color,
 }
 
 #[derive(Copy, Clone)]
@@ -45,6 +86,7 @@ pub struct GasParams {
     pub dist_amp: f32,
     pub noise_freq: f32,
     pub storm_speed: f32,
+    pub basis: NoiseBasis,
     pub c_a: Color, pub c_b: Color, pub c_c: Color,
 }
 
@@ -52,15 +94,346 @@ pub struct GasParams {
 pub struct IceParams {
     pub freq: f32,
     pub marbling: f32,
+    pub basis: NoiseBasis,
+    // red de grietas (Worley F2-F1)
+    pub crack_scale: f32,
+    pub crack_width: f32,
+    pub crack_metric: Metric,
+    pub k_atm: f32, // densidad de la atmósfera (0 = sin atmósfera)
     pub c_ice: Color, pub c_snow: Color, pub c_crack: Color,
 }
 
+#[derive(Copy, Clone)]
+pub struct LavaParams {
+    pub crack_scale: f32,
+    pub crack_width: f32,
+    pub flow_speed: f32,
+    pub magma_temp: f32, // K en el centro de las fisuras
+    pub basis: NoiseBasis,
+    pub c_crust: Color, pub c_magma_cool: Color, pub c_magma_hot: Color,
+}
+
+#[derive(Copy, Clone)]
+pub struct DesertParams {
+    pub dune_wavelength: f32, // distancia entre crestas, en radianes de la esfera
+    pub wind_dir: f32,        // ángulo del viento en lat/lon (0 = hacia el este)
+    pub sharpness: f32,       // 1 = crestas suaves, más = más afiladas
+    pub lakes: f32,           // fracción de lagos secos
+    pub mesas: f32,           // fracción de mesetas
+    pub basis: NoiseBasis,
+    pub c_sand_light: Color, pub c_sand_dark: Color, pub c_salt: Color, pub c_rock: Color,
+}
+
+#[derive(Copy, Clone)]
+pub struct MoonParams {
+    pub crater_freq: f32,    // celdas de los cráteres más grandes
+    pub crater_octaves: u32, // tamaños, cada uno la mitad del anterior
+    pub size_power: f32,     // exponente de N(>D) ∝ D^-size_power
+    pub density: f32,        // probabilidad de cráter en una celda grande
+    pub rim_height: f32,
+    pub ray_ejecta: f32,     // brillo de los rayos de los cráteres jóvenes
+    pub relief: f32,
+    pub maria: f32,          // fracción cubierta por mares de basalto
+    pub albedo: f32,         // reflectividad del regolito, escala los colores
+    pub basis: NoiseBasis,
+    pub c_highland: Color, pub c_mare: Color, pub c_ray: Color,
+}
+
+// Capa de atmósfera de Rocky e Ice, en radios del planeta; cada cuerpo la escala con su k_atm
+#[derive(Copy, Clone)]
+pub struct AtmosphereParams {
+    pub radius: f32,                       // radio exterior de la capa
+    pub h_rayleigh: f32, pub h_mie: f32,   // alturas de escala
+    pub beta_rayleigh: Color,              // coeficientes de dispersión a nivel del suelo
+    pub beta_mie: f32,
+    pub mie_g: f32,                        // anisotropía de Henyey-Greenstein
+    pub sun: f32,                          // intensidad de la luz principal
+}
+
+// Anillos en el plano ecuatorial; radios en radios del planeta
+#[derive(Copy, Clone)]
+pub struct RingParams {
+    pub rin: f32, pub rout: f32,
+    pub opacity: f32,
+    pub ringlets: f32,               // frecuencia de las bandas finas
+    pub gap_pos: f32, pub gap_width: f32, // división tipo Cassini, en fracción de (rout - rin)
+    pub basis: NoiseBasis,
+    pub c_inner: Color, pub c_outer: Color,
+}
+
+// Capa de nubes opcional sobre cualquier planeta; altitud en radios del planeta
+#[derive(Copy, Clone)]
+pub struct CloudParams {
+    pub coverage: f32,  // fracción del cielo cubierta, 0..1
+    pub altitude: f32,
+    pub scale: f32,
+    pub wind: f32,      // rad por unidad de tiempo en el ecuador
+    pub wind_mid: f32,  // rad por unidad de tiempo en el centro de las franjas a contracorriente
+    pub bands: f32,     // perfil cos(bands·lat): 3 = corriente ecuatorial y otra opuesta hacia los 60°
+    pub octaves: u32,
+    pub basis: NoiseBasis,
+    pub color: Color,
+}
+
+impl Default for CloudParams {
+    fn default() -> Self {
+        CloudParams {
+            coverage: 0.5,
+            altitude: 0.02,
+            scale: 3.0,
+            wind: 0.4,
+            wind_mid: -0.4,
+            bands: 3.0,
+            octaves: 5,
+            basis: NoiseBasis::Value,
+            color: hex_rgb_u8("#f4f6fa"),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct StarParams {
+    pub temperature: f32,     // K en el centro del disco
+    pub granulation: f32,     // celdas de convección por radio
+    pub spot_density: f32,    // 0 = sin manchas, 1 = muy activa
+    pub flare_activity: f32,  // fáculas, fulguraciones y brillo de la corona
+    pub basis: NoiseBasis,
+}
+
+// Tamaños en radios del núcleo (el radio del cuerpo). La cola se alarga como ref_distance/d y
+// brilla como (ref_distance/d)², con d la distancia a la luz principal.
+#[derive(Copy, Clone)]
+pub struct CometParams {
+    pub roughness: f32,       // 0 = núcleo esférico
+    pub coma_radius: f32,
+    pub coma_brightness: f32,
+    pub tail_length: f32,
+    pub ion_brightness: f32,
+    pub dust_brightness: f32,
+    pub dust_curvature: f32,  // 0 = recta como la de iones
+    pub ref_distance: f32,    // distancia a la luz (unidades de la escena) con actividad 1
+    pub basis: NoiseBasis,
+    pub c_nucleus: Color, pub c_coma: Color, pub c_ion: Color, pub c_dust: Color,
+}
+
+#[derive(Copy, Clone)]
+pub struct AsteroidParams {
+    pub roughness: f32,   // 0 = esfera
+    pub lump_freq: f32,
+    pub elongation: f32,  // 0 = sin alargar
+    pub crater_freq: f32,
+    pub crater_depth: f32,
+    pub albedo: f32,      // reflectividad del regolito, escala los colores
+    pub basis: NoiseBasis,
+    pub c_rock: Color, pub c_dust: Color,
+}
+
+// Cielo de fondo de la escena, fijo en el mundo. Brillos en 0 apagan cada capa.
+#[derive(Copy, Clone, Debug)]
+pub struct BackgroundParams {
+    pub seed: u64,
+    pub stars: f32,
+    pub star_density: f32, // fracción de celdas del cielo con estrella
+    pub milky_way: f32,
+    pub pole: Vec3,        // polo galáctico: la Vía Láctea es su ecuador
+    pub nebulae: f32,
+    pub nebula_scale: f32,
+    pub c_nebula1: Color, pub c_nebula2: Color,
+}
+
+impl Default for BackgroundParams {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            stars: 1.0,
+            star_density: 0.08,
+            milky_way: 1.0,
+            pole: vec3(0.3, 0.8, -0.5),
+            nebulae: 1.0,
+            nebula_scale: 4.0,
+            c_nebula1: hex_rgb_u8("#d8405e"),
+            c_nebula2: hex_rgb_u8("#3ec1c9"),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
 pub struct Params {
     pub common: CommonParams,
     pub disk: DiskParams,
     pub rocky: RockyParams,
     pub gas: GasParams,
     pub ice: IceParams,
+    pub lava: LavaParams,
+    pub desert: DesertParams,
+    pub moon: MoonParams,
+    pub comet: CometParams,
+    pub asteroid: AsteroidParams,
+    pub atmosphere: AtmosphereParams,
+    pub ring: RingParams,
+    pub star: StarParams,
+    pub clouds: Option<CloudParams>,
+}
+
+// Valores del visor original
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            common: CommonParams {
+                warm: hex_rgb_u8("#ffb347"),
+            },
+            disk: DiskParams {
+                // en radios de Schwarzschild: el disco empieza en la ISCO (3 r_s)
+                rin: 3.0,
+                rout: 8.0,
+                bands_w: 22.0,
+                bands_phi: 0.3,
+                noise_freq: 2.8,
+                noise_amp: 0.08,
+                beaming: 1.0,
+                temp_in: 6000.0,
+                // 0 = paleta c1..c3 de siempre, 1 = color de cuerpo negro a la temperatura local
+                temp_mix: 0.0,
+                basis: NoiseBasis::Value,
+                c1: hex_rgb_u8("#ff9a00"),
+                c2: hex_rgb_u8("#ffd65c"),
+                c3: hex_rgb_u8("#fff3e0"),
+            },
+            rocky: RockyParams {
+                bioma_freq: 7.0,
+                height_freq: 8.0,
+                grad_amp: 0.35,
+                k_atm: 1.0,
+                basis: NoiseBasis::Value,
+                crater_freq: 6.0,
+                crater_depth: 0.0,
+                sea_level: 0.48,
+                shininess: 80.0,
+                city_lights: 1.5,
+                city_freq: 24.0,
+                c_land1: hex_rgb_u8("#6b4f2a"),
+                c_land2: hex_rgb_u8("#9db36b"),
+                c_ocean: hex_rgb_u8("#1c3b6b"),
+                c_shallow: hex_rgb_u8("#2f7f95"),
+                c_city: hex_rgb_u8("#ffc46b"),
+            },
+            gas: GasParams {
+                k_bands: 16.0,
+                dist_amp: 0.06,
+                noise_freq: 3.0,
+                storm_speed: 0.12,
+                basis: NoiseBasis::Value,
+                c_a: hex_rgb_u8("#f0e1c2"),
+                c_b: hex_rgb_u8("#d9a066"),
+                c_c: hex_rgb_u8("#9b6b43"),
+            },
+            ice: IceParams {
+                freq: 10.0,
+                marbling: 1.6,
+                basis: NoiseBasis::Value,
+                crack_scale: 4.0,
+                crack_width: 0.05,
+                crack_metric: Metric::Euclidean,
+                k_atm: 0.4,
+                c_ice: hex_rgb_u8("#9fd0ff"),
+                c_snow: hex_rgb_u8("#e6f4ff"),
+                c_crack: hex_rgb_u8("#284a73"),
+            },
+            lava: LavaParams {
+                crack_scale: 5.0,
+                crack_width: 0.06,
+                flow_speed: 0.3,
+                magma_temp: 1800.0,
+                basis: NoiseBasis::Gradient,
+                c_crust: hex_rgb_u8("#2b2422"),
+                c_magma_cool: hex_rgb_u8("#c0301a"),
+                c_magma_hot: hex_rgb_u8("#ffd36a"),
+            },
+            desert: DesertParams {
+                dune_wavelength: 0.05,
+                wind_dir: 0.5,
+                sharpness: 2.0,
+                lakes: 0.15,
+                mesas: 0.12,
+                basis: NoiseBasis::Gradient,
+                c_sand_light: hex_rgb_u8("#e8c48a"),
+                c_sand_dark: hex_rgb_u8("#b9824a"),
+                c_salt: hex_rgb_u8("#eee8dc"),
+                c_rock: hex_rgb_u8("#8a4b2e"),
+            },
+            moon: MoonParams {
+                crater_freq: 3.0,
+                crater_octaves: 5,
+                size_power: 2.0,
+                density: 0.5,
+                rim_height: 0.35,
+                ray_ejecta: 0.6,
+                relief: 1.0,
+                maria: 0.35,
+                albedo: 0.8,
+                basis: NoiseBasis::Value,
+                c_highland: hex_rgb_u8("#a9a49c"),
+                c_mare: hex_rgb_u8("#56534f"),
+                c_ray: hex_rgb_u8("#dcd9d2"),
+            },
+            comet: CometParams {
+                roughness: 0.45,
+                coma_radius: 18.0,
+                coma_brightness: 1.0,
+                tail_length: 100.0,
+                ion_brightness: 1.0,
+                dust_brightness: 1.0,
+                dust_curvature: 0.6,
+                ref_distance: 10.0,
+                basis: NoiseBasis::Value,
+                c_nucleus: hex_rgb_u8("#4a4440"),
+                c_coma: hex_rgb_u8("#b9f5d0"),
+                c_ion: hex_rgb_u8("#5f9dff"),
+                c_dust: hex_rgb_u8("#ffdc9a"),
+            },
+            asteroid: AsteroidParams {
+                roughness: 0.35,
+                lump_freq: 1.4,
+                elongation: 0.4,
+                crater_freq: 3.0,
+                crater_depth: 0.6,
+                albedo: 0.8,
+                basis: NoiseBasis::Value,
+                c_rock: hex_rgb_u8("#6f665d"),
+                c_dust: hex_rgb_u8("#9b8b78"),
+            },
+            // capa más gruesa que la terrestre para que se lea a esta escala, con la misma
+            // profundidad óptica vertical aproximada
+            atmosphere: AtmosphereParams {
+                radius: 1.15,
+                h_rayleigh: 0.04,
+                h_mie: 0.012,
+                beta_rayleigh: rgb(1.15, 2.7, 6.65),
+                beta_mie: 2.0,
+                mie_g: 0.76,
+                sun: 16.0,
+            },
+            ring: RingParams {
+                rin: 1.25,
+                rout: 2.2,
+                opacity: 0.85,
+                ringlets: 40.0,
+                gap_pos: 0.62,
+                gap_width: 0.05,
+                basis: NoiseBasis::Value,
+                c_inner: hex_rgb_u8("#e8dcc8"),
+                c_outer: hex_rgb_u8("#b9a994"),
+            },
+            star: StarParams {
+                temperature: 5800.0,
+                granulation: 40.0,
+                spot_density: 0.35,
+                flare_activity: 0.5,
+                basis: NoiseBasis::Simplex,
+            },
+            clouds: None,
+        }
+    }
 }
 
 #[inline]
@@ -76,92 +449,241 @@ fn nl_mix(n: Vec3, l0: Vec3, l1: Vec3) -> f32 {
 }
 
 pub fn shade(ctx: &ShadingCtx, body: Body, params: &Params) -> Color {
-    match body {
-        Body::BlackHole    => shade_black_hole(ctx),
-        Body::AccretionDisk=> shade_accretion(ctx, &params.disk),
-        Body::Rocky        => shade_rocky(ctx, &params.common, &params.rocky),
-        Body::GasGiant     => shade_gas_giant(ctx, &params.common, &params.gas),
-        Body::Ice          => shade_ice(ctx, &params.common, &params.ice),
-    }.clamp01()
+    let (lit, glow) = shade_parts(ctx, body, params);
+    (lit + glow).clamp01()
 }
 
-fn shade_black_hole(ctx: &ShadingCtx) -> Color {
-    // negro puro + halo suave en el borde (no físico, solo estético)
-    let _r = ctx.p.length(); // si usas pantalla, puedes usar coord en viewspace
-    // halo según rim con la vista
-    let glow = rim_term(ctx.n, ctx.v, 3.5);
-    rgb(0.06, 0.03, 0.10) * (glow*0.25) // leve morado
+// The color split in two: what the surface reflects from the lights, which shadows darken, and
+// what it gives off by itself (city lights, lava, stars, disks), which they must not.
+pub fn shade_parts(ctx: &ShadingCtx, body: Body, params: &Params) -> (Color, Color) {
+    let none = rgb(0.0, 0.0, 0.0);
+    let (lit, glow) = match body {
+        // sin superficie: el render lo dibuja integrando geodésicas (ver `blackhole`)
+        Body::BlackHole    => (none, none),
+        Body::AccretionDisk=> (none, shade_accretion(ctx, &params.disk)),
+        Body::Rocky        => shade_rocky(ctx, &params.rocky),
+        Body::GasGiant     => (shade_gas_giant(ctx, &params.common, &params.gas), none),
+        Body::Ice          => (shade_ice(ctx, &params.ice), none),
+        Body::Star         => (none, shade_star(ctx, &params.star)),
+        Body::Lava         => shade_lava(ctx, &params.lava),
+        Body::Desert       => (shade_desert(ctx, &params.desert), none),
+        Body::Moon         => (shade_moon(ctx, &params.moon), none),
+        Body::Comet        => (shade_nucleus(ctx, &params.comet), none),
+        Body::Asteroid     => (shade_asteroid(ctx, &params.asteroid), none),
+    };
+    // las nubes tapan el brillo propio igual que tapan el suelo
+    match params.clouds {
+        Some(c) if matches!(body, Body::Rocky | Body::GasGiant | Body::Ice | Body::Lava | Body::Desert) => {
+            let (col, cover) = composite_clouds(ctx, lit, &c);
+            (col, glow * (1.0 - cover))
+        }
+        _ => (lit, glow),
+    }
 }
 
 fn shade_accretion(ctx: &ShadingCtx, p: &DiskParams) -> Color {
     // asumimos disco en plano XZ: usa la posición (p) proyectada
     let r = (ctx.p.x*ctx.p.x + ctx.p.z*ctx.p.z).sqrt();
-    let mut col = rgb(0.0,0.0,0.0);
 
-    // 1) emisión radial (más caliente cerca del borde interno)
-    let heat = ((-(r - p.rin)*3.0).exp()).clamp(0.0, 1.0);
+    // 1) perfil de temperatura de Shakura–Sunyaev, T ∝ r^-3/4 desde el borde interno;
+    //    la emisión total va con T^4
+    let temp = p.temp_in * (r / p.rin).max(1.0).powf(-0.75);
+    let heat = (temp / p.temp_in).powi(4);
 
     // 2) bandas radiales
     let bands = (p.bands_w*r + p.bands_phi).sin()*0.5 + 0.5;
 
     // 3) granulado + animación
     let rp = vec3(ctx.p.x, 0.0, ctx.p.z) * p.noise_freq + vec3(ctx.t*0.05, 0.0, ctx.t*0.05);
-    let g = fbm3(rp, 4, 2.0, 0.5);
+    let g = fbm(p.basis, rp, ctx.seed, 4, 2.0, 0.5);
     let distort = (bands + p.noise_amp*(g-0.5)).clamp(0.0,1.0);
 
     // paleta cálida 3 tonos
     let warm = palette3(distort, p.c1, p.c2, p.c3);
 
-    // 4) beaming falso (lado que viene hacia la cámara más brillante)
-    let ndv = saturate(ctx.n.dot(-ctx.v));
-    let beam = 0.6 + p.beaming * ndv.powf(3.0);
+    // 4) Doppler relativista + corrimiento gravitacional: gas en órbita kepleriana
+    //    (r en radios de Schwarzschild) visto en la dirección ctx.v
+    //    paleta: se remuestrea como espectro; cuerpo negro: se ve a temperatura g·T
+    let g = disk_shift(ctx.p, ctx.v, r).powf(p.beaming);
+    let shifted_palette = redshift_color(warm, g) * (g*g*g);
+    let shifted_bb = blackbody(temp * g) * (g*g*g*g) * (0.55 + 0.45*distort);
+    let shifted = shifted_palette.mix(shifted_bb, p.temp_mix);
 
     // 5) apagar fuera del disco y recortar interior duro
     let inside = ((r - p.rin) / (p.rout - p.rin)).clamp(0.0, 1.0);
-    let ring_mask = (1.0 - (1.0 - inside).powf(16.0)) * (1.0 - ((r - p.rout).max(0.0)).min(1.0));
+    let ring_mask = (1.0 - (1.0 - inside).powf(16.0)) * (1.0 - (r - p.rout).clamp(0.0, 1.0));
 
-    col = warm * (0.35 + 0.65*heat) * beam * ring_mask;
-    col
+    shifted * (0.15 + 0.85*heat) * ring_mask
 }
 
-fn shade_rocky(ctx: &ShadingCtx, common: &CommonParams, p: &RockyParams) -> Color {
-    let (lat, lon) = lat_lon_from_normal(ctx.n);
-    // 1) biomas base
-    let k = fbm3(vec3(lat*p.bioma_freq, lon*p.bioma_freq, ctx.seed), 5, 2.0, 0.5);
-    let mut base = palette3(k, p.c_land1, p.c_land2, p.c_ocean);
+// Frequency ratio g = ν_obs / ν_emit for disk gas at `pos` seen along `to_eye`:
+// Keplerian speed of a circular orbit measured by a static observer, β = sqrt(1 / (2(r - 1))),
+// prograde around +Y, times the gravitational redshift sqrt(1 - 1/r).
+fn disk_shift(pos: Vec3, to_eye: Vec3, r: f32) -> f32 {
+    let r = r.max(1.05);
+    let beta = (0.5 / (r - 1.0)).sqrt().min(0.99);
+    let gamma = 1.0 / (1.0 - beta*beta).sqrt();
+    let tangent = vec3(pos.z, 0.0, -pos.x) / r;
+    let doppler = 1.0 / (gamma * (1.0 - beta * tangent.dot(to_eye)));
+    doppler * (1.0 - 1.0/r).sqrt()
+}
 
-    // 2) altura sintética + sombreado falso
-    let h = fbm3(vec3(lat*p.height_freq, lon*p.height_freq, ctx.seed+17.0), 4, 2.1, 0.5);
-    let nl = nl_mix(ctx.n, ctx.l0, ctx.l1);
-    let shade = 0.6 + 0.4 * (nl + 0.15*(h-0.5)).clamp(0.0,1.0);
-    base = base * shade;
+// Reads a rest-frame color as a spectrum sampled at the R, G, B wavelengths and resamples it
+// at λ_emit = g·λ_obs: g > 1 turns it bluer, g < 1 redder. Intensity is left to the caller.
+fn redshift_color(c: Color, g: f32) -> Color {
+    const LAMBDA: [f32; 3] = [610.0, 550.0, 465.0];
+    let spectrum = |l: f32| {
+        // lineal entre muestras, constante fuera de ellas
+        let l = l.clamp(LAMBDA[2], LAMBDA[0]);
+        let (a, b, la, lb) = if l > LAMBDA[1] { (c.y, c.x, LAMBDA[1], LAMBDA[0]) } else { (c.z, c.y, LAMBDA[2], LAMBDA[1]) };
+        a + (b - a) * (l - la) / (lb - la)
+    };
+    vec3(spectrum(LAMBDA[0]*g), spectrum(LAMBDA[1]*g), spectrum(LAMBDA[2]*g))
+}
+
+fn shade_rocky(ctx: &ShadingCtx, p: &RockyParams) -> (Color, Color) {
+    let (lat, _) = lat_lon_from_normal(ctx.n);
+    // 1) biomas base (sobre la dirección, como la altura: sin costura en el meridiano)
+    let k = fbm(p.basis, ctx.n * (p.bioma_freq / PI), ctx.seed, 5, 2.0, 0.5);
+    let mut base = p.c_land1.mix(p.c_land2, k);
+
+    // 2) altura sintética
+    let h = rocky_height(ctx.n, ctx.seed, p);
 
     // 3) montañas/nieves
     let peaks = ((h - 0.62)/0.08).clamp(0.0,1.0);
     let snow = hex_rgb_u8("#e6edf3");
     base = base.mix(snow, peaks);
 
+    // 3b) cráteres: cuenco oscuro dentro del radio de cada celda, borde claro alrededor
+    if p.crater_depth > 0.0 {
+        base = base * (1.0 + p.crater_depth * crater_profile(ctx.n * p.crater_freq, ctx.seed.wrapping_add(13)));
+    }
+
+    // luz con la normal inclinada por el gradiente del relieve (montañas y cráteres)
+    let nb = bump_normal(ctx.n, p.grad_amp, |n| rocky_relief(n, ctx.seed, p));
+    let shade = 0.2 + 0.8 * nl_mix(nb, ctx.l0, ctx.l1);
+    base = base * shade;
+
+    // 3c) océano bajo el nivel del mar, con costa suave
+    let depth = p.sea_level - h;
+    if depth > -0.01 {
+        let water = shade_water(ctx, p, depth.max(0.0));
+        base = water.mix(base, smoothstep(0.0, -0.01, depth));
+    }
+
     // 4) polos (latitud 0..1; polos cerca de 0 y 1)
-    let pole_mask = ((lat-0.5).abs()-0.35);
+    let pole_mask = (lat-0.5).abs()-0.35;
     let pole = (1.0 - (pole_mask/0.15).clamp(0.0,1.0)).powf(2.0);
-    base = base.mix(snow, 0.35*pole);
+    base = base.mix(snow * shade, 0.35*pole);
+
+    // 5) luces nocturnas encima de todo, aparte: no las oscurecen las sombras
+    // (la atmósfera la pone el render como capa aparte, ver `atmosphere`)
+    (base.clamp01(), city_lights(ctx, p, h))
+}
+
+// Night-side city lights: a population mask (fbm regions dotted with Worley towns) on land in
+// the mid latitudes, fading in past the terminator.
+fn city_lights(ctx: &ShadingCtx, p: &RockyParams, h: f32) -> Color {
+    if p.city_lights <= 0.0 { return rgb(0.0, 0.0, 0.0); }
+    let night = smoothstep(0.05, -0.15, ctx.n.dot(ctx.l0));
+    let land = smoothstep(p.sea_level + 0.005, p.sea_level + 0.03, h) * (1.0 - smoothstep(0.6, 0.7, h));
+    let lat = ctx.n.y.abs();
+    let temperate = smoothstep(0.1, 0.3, lat) * (1.0 - smoothstep(0.65, 0.8, lat));
+    let mask = night * land * temperate;
+    if mask <= 0.0 { return rgb(0.0, 0.0, 0.0); }
+
+    let q = ctx.n * p.city_freq;
+    let regions = fbm(p.basis, q * 0.15, ctx.seed.wrapping_add(41), 3, 2.0, 0.5);
+    let towns = worley3(q, ctx.seed.wrapping_add(42), Metric::Euclidean);
+    let core = 1.0 - smoothstep(0.0, 0.3 + 0.25*towns.rand, towns.f1);
+    let sprawl = fbm(p.basis, q * 3.0, ctx.seed.wrapping_add(43), 3, 2.0, 0.5);
+    let population = smoothstep(0.4, 0.6, regions) * (core + 0.5 * smoothstep(0.5, 0.75, sprawl));
+    p.c_city * (p.city_lights * mask * population)
+}
+
+// Light a surface gives off by itself, ignoring the lights (the viewer's emissive-only view):
+// city lights, lava fissures, everything from stars and accretion disks, nothing elsewhere.
+pub fn emission(ctx: &ShadingCtx, body: Body, params: &Params) -> Color {
+    shade_parts(ctx, body, params).1.clamp01()
+}
+
+// Terrain height in [0, 1], noise over the direction itself so it is seamless at the date line
+// and smooth at the poles; height_freq keeps its scale (features per pole-to-pole span).
+fn rocky_height(n: Vec3, seed: u64, p: &RockyParams) -> f32 {
+    fbm(p.basis, n * (p.height_freq / PI), seed.wrapping_add(17), 4, 2.1, 0.5)
+}
+
+// Altura que ve la luz: el terreno (plano bajo el mar) más el perfil de los cráteres.
+fn rocky_relief(n: Vec3, seed: u64, p: &RockyParams) -> f32 {
+    let mut h = rocky_height(n, seed, p).max(p.sea_level);
+    if p.crater_depth > 0.0 {
+        h += 0.08 * p.crater_depth * crater_profile(n * p.crater_freq, seed.wrapping_add(13));
+    }
+    h
+}
+
+// Normal perturbed by the gradient of `height` over the sphere, by finite differences along the
+// local east/north tangents.
+fn bump_normal(n: Vec3, amp: f32, height: impl Fn(Vec3) -> f32) -> Vec3 {
+    const EPS: f32 = 0.002;
+    if amp <= 0.0 { return n; }
+    let up = if n.y.abs() > 0.999 { vec3(1.0, 0.0, 0.0) } else { vec3(0.0, 1.0, 0.0) };
+    let east = up.cross(n).normalized();
+    let north = n.cross(east);
+    let c = height(n);
+    let de = (height((n + east*EPS).normalized()) - c) / EPS;
+    let dn = (height((n + north*EPS).normalized()) - c) / EPS;
+    (n - (east*de + north*dn) * amp).normalized()
+}
 
-    // 5) atmósfera fina (rim)
-    let rim = rim_term(ctx.n, ctx.v, 2.5);
-    let atm = common.cool * (0.12*rim);
-    (base + atm).clamp01()
+// Same as bump_normal for a height whose 3D gradient is known: only its tangential part tilts n.
+fn bump_from_gradient(n: Vec3, amp: f32, grad: Vec3) -> Vec3 {
+    if amp <= 0.0 { return n; }
+    (n - (grad - n * grad.dot(n)) * amp).normalized()
+}
+
+// Agua: color según profundidad (más clara hacia la costa), cielo reflejado con Fresnel
+// (Schlick, F0 = 0.02) y brillo del sol de Blinn-Phong con l0.
+fn shade_water(ctx: &ShadingCtx, p: &RockyParams, depth: f32) -> Color {
+    let sky = hex_rgb_u8("#9cc3e6");
+    let body = p.c_shallow.mix(p.c_ocean, smoothstep(0.0, 0.12, depth));
+    let nl = nl_mix(ctx.n, ctx.l0, ctx.l1);
+    let diffuse = body * (0.15 + 0.85*nl);
+
+    let schlick = |c: f32| 0.02 + 0.98*(1.0 - saturate(c)).powi(5);
+    let fresnel = schlick(ctx.n.dot(ctx.v));
+    let mut col = diffuse.mix(sky * (0.1 + 0.9*nl), fresnel);
+
+    let nl0 = ctx.n.dot(ctx.l0);
+    if nl0 > 0.0 {
+        let half = (ctx.l0 + ctx.v).normalized();
+        // normalizado para que subir shininess concentre el brillo en vez de apagarlo
+        let spec = saturate(ctx.n.dot(half)).powf(p.shininess) * (p.shininess + 8.0) / 8.0;
+        col += rgb(1.0, 0.97, 0.9) * (spec * schlick(ctx.v.dot(half)) * nl0 * 4.0);
+    }
+    col
+}
+
+// Relieve de un campo de cráteres en [-1, 1]: negativo en el cuenco, positivo en el borde.
+fn crater_profile(q: Vec3, seed: u64) -> f32 {
+    let w = worley3(q, seed, Metric::Euclidean);
+    let r = 0.2 + 0.25*w.rand; // radio por celda
+    let d = w.f1 / r;
+    let bowl = if d < 1.0 { -(1.0 - d*d) } else { 0.0 };
+    let rim = (-((d - 1.0)/0.18).powi(2)).exp();
+    (0.7*bowl + 0.5*rim).clamp(-1.0, 1.0)
 }
 
 fn shade_gas_giant(ctx: &ShadingCtx, common: &CommonParams, p: &GasParams) -> Color {
-    let (mut lat, mut lon) = lat_lon_from_normal(ctx.n);
+    let (lat, lon) = lat_lon_from_normal(ctx.n);
 
-    // 1) bandas latitudinales
-    let mut bands = (p.k_bands*lat*2.0*PI).sin()*0.5 + 0.5;
+    // 1) distorsión por ruido (ondula límites)
+    let d = fbm(p.basis, vec3(ctx.p.x*p.noise_freq, ctx.p.y*p.noise_freq, ctx.p.z*p.noise_freq), ctx.seed, 4, 2.0, 0.5);
+    let lat = (lat + p.dist_amp*(d-0.5)).clamp(0.0,1.0);
 
-    // 2) distorsión por ruido (ondula límites)
-    let d = fbm3(vec3(ctx.p.x*p.noise_freq, ctx.p.y*p.noise_freq, ctx.p.z*p.noise_freq), 4, 2.0, 0.5);
-    lat = (lat + p.dist_amp*(d-0.5)).clamp(0.0,1.0);
-    bands = (p.k_bands*lat*2.0*PI).sin()*0.5 + 0.5;
+    // 2) bandas latitudinales
+    let bands = (p.k_bands*lat*2.0*PI).sin()*0.5 + 0.5;
 
     // 3) tormentas / gran mancha (elipse en lat/lon)
     let storm_lon = (lon + ctx.t*p.storm_speed).fract();
@@ -178,24 +700,660 @@ fn shade_gas_giant(ctx: &ShadingCtx, common: &CommonParams, p: &GasParams) -> Co
     let nl = nl_mix(ctx.n, ctx.l0, ctx.l1);
     col = col * (0.45 + 0.55*nl);
     let rim = rim_term(ctx.n, ctx.v, 2.8);
-    col = col + common.warm * (0.10*rim);
+    col += common.warm * (0.10*rim);
 
     col.clamp01()
 }
 
-fn shade_ice(ctx: &ShadingCtx, common: &CommonParams, p: &IceParams) -> Color {
+fn shade_lava(ctx: &ShadingCtx, p: &LavaParams) -> (Color, Color) {
+    let (edge, fine, along) = lava_cracks(ctx, p);
+
+    // costra: placas oscuras, más calientes (rojizas) junto a las fisuras y frías lejos de ellas
+    let cooling = smoothstep(0.0, p.crack_width*8.0, edge);
+    let grain = fbm(p.basis, ctx.n*p.crack_scale*6.0, ctx.seed.wrapping_add(52), 3, 2.0, 0.5);
+    let crust = p.c_crust * (0.8 + 0.4*grain);
+    let warm = crust.mix(p.c_magma_cool * 0.35, 0.6);
+    let crust = warm.mix(crust, cooling);
+
+    let nl = nl_mix(ctx.n, ctx.l0, ctx.l1);
+    (crust * (0.2 + 0.8*nl), lava_glow(ctx, p, edge, fine, along))
+}
+
+// Distancias a las fisuras de lava: red Worley principal (F2-F1) y una secundaria más fina,
+// y la dirección de la fisura principal más cercana (tangente, a lo largo de la grieta).
+fn lava_cracks(ctx: &ShadingCtx, p: &LavaParams) -> (f32, f32, Vec3) {
+    let q = ctx.n * p.crack_scale;
+    let seed = ctx.seed.wrapping_add(50);
+    let wide = worley3(q, seed, Metric::Euclidean);
+    let fine = worley3(q * 2.3, ctx.seed.wrapping_add(51), Metric::Euclidean);
+    // la grieta es el plano bisector de las dos celdas; ordenadas para que el sentido del flujo
+    // sea el mismo a ambos lados
+    let (a, b) = if wide.cell < wide.cell2 { (wide.cell, wide.cell2) } else { (wide.cell2, wide.cell) };
+    let along = ctx.n.cross(worley_point(b, seed) - worley_point(a, seed)).normalized();
+    (wide.edge(), fine.edge(), along)
+}
+
+// Incandescencia del magma: cuerpo negro a una temperatura que baja con la distancia a la
+// fisura, con pulsos que recorren las grietas con el tiempo.
+fn lava_glow(ctx: &ShadingCtx, p: &LavaParams, edge: f32, fine: f32, along: Vec3) -> Color {
+    let open = (1.0 - smoothstep(0.0, p.crack_width, edge)).max(0.5 * (1.0 - smoothstep(0.0, p.crack_width*0.5, fine)));
+    let halo = (-edge / (p.crack_width*2.5)).exp() * 0.35;
+    let heat = open.max(halo);
+    if heat <= 0.001 { return rgb(0.0, 0.0, 0.0); }
+
+    // flujo: ruido arrastrado a lo largo de la grieta con el tiempo, solo dentro de la fisura;
+    // el halo sobre la costra queda quieto
+    let drift = along * (ctx.t * p.flow_speed);
+    let flow = fbm(p.basis, ctx.n*p.crack_scale*3.0 - drift, ctx.seed.wrapping_add(53), 3, 2.0, 0.5);
+    let hot = saturate(heat * (1.0 + 0.8*(flow - 0.5)*open));
+
+    let temp = p.magma_temp * (0.55 + 0.45*hot);
+    let tint = p.c_magma_cool.mix(p.c_magma_hot, hot*hot);
+    // sobreexpuesto a propósito: el recorte satura los centros hacia amarillo
+    blackbody(temp) * tint * (hot * (0.3 + 2.7*hot*hot))
+}
+
+fn shade_desert(ctx: &ShadingCtx, p: &DesertParams) -> Color {
+    let f = desert_features(ctx.n, ctx.seed, p);
+
+    // arena: más clara en las crestas; sal en los lagos secos; roca con estratos en las mesetas
+    let mut base = p.c_sand_dark.mix(p.c_sand_light, f.dunes);
+    base = base.mix(p.c_salt, f.lake);
+    let strata = (f.height * 60.0).sin()*0.5 + 0.5;
+    base = base.mix(p.c_rock * (0.8 + 0.3*strata), f.mesa);
+
+    // las laderas de sotavento y los acantilados de las mesetas se ven con el gradiente de altura
+    let nb = bump_from_gradient(ctx.n, 1.0, f.grad);
+    let shade = 0.2 + 0.8 * nl_mix(nb, ctx.l0, ctx.l1);
+    (base * shade).clamp01()
+}
+
+struct DesertFeatures {
+    height: f32,
+    grad: Vec3, // de la altura, respecto de n
+    dunes: f32, // 0 valle .. 1 cresta
+    lake: f32,
+    mesa: f32,
+}
+
+// Dunas transversales al viento, que sopla wind_dir desde el este del marco local este/norte.
+// Ese viento da la vuelta al planeta, así que no hay una fase única sin costura: las dunas se
+// calculan por parches (celdas Worley de ~8 longitudes de onda) en el marco del centro de cada
+// uno, y los dos parches más cercanos se funden en una franja estrecha (uniones en "Y").
+// El gradiente es analítico; el de la mezcla entre parches (franja estrecha) y el de los
+// escalones de las mesetas (saltos) no se incluyen.
+fn desert_features(n: Vec3, seed: u64, p: &DesertParams) -> DesertFeatures {
+    let k = 1.0 / p.dune_wavelength.max(1e-3);
+    let patches = worley3(n * (k / 8.0), seed.wrapping_add(60), Metric::Euclidean);
+    let blend = 0.5 + 0.5*smoothstep(0.0, 0.15, patches.edge());
+    let (d, g) = dune_patch(n, patches.cell, k, seed, p);
+    let (mut dunes, mut dunes_grad) = (blend * d, g * blend);
+    if blend < 1.0 {
+        let (d, g) = dune_patch(n, patches.cell2, k, seed, p);
+        dunes += (1.0 - blend) * d;
+        dunes_grad += g * (1.0 - blend);
+    }
+
+    // lagos secos (cuencas planas) y mesetas escalonadas, a escala mucho mayor que las dunas
+    let (basin, basin_grad) = fbm_grad(p.basis, n*2.5, seed.wrapping_add(65), 4, 2.0, 0.5);
+    let (l0, l1) = (0.34 + 0.3*p.lakes, 0.32 + 0.3*p.lakes);
+    let lake = smoothstep(l0, l1, basin);
+    let lake_grad = basin_grad * (2.5 * smoothstep_d(l0, l1, basin));
+    let (uplift, uplift_grad) = fbm_grad(p.basis, n*3.5, seed.wrapping_add(66), 4, 2.0, 0.5);
+    let (m0, m1) = (0.61 - 0.3*p.mesas, 0.63 - 0.3*p.mesas);
+    let mesa = smoothstep(m0, m1, uplift);
+    let mesa_grad = uplift_grad * (3.5 * smoothstep_d(m0, m1, uplift));
+
+    let (dry, flat) = (1.0 - lake, 1.0 - mesa);
+    let sand = dunes * 0.02 * dry * flat;
+    let sand_grad = (dunes_grad * (dry * flat) - lake_grad * (dunes * flat) - mesa_grad * (dunes * dry)) * 0.02;
+    let step = 0.06 + 0.01*(uplift*8.0).floor();
+    let height = sand + mesa * step;
+    let grad = sand_grad + mesa_grad * step;
+    DesertFeatures { height, grad, dunes, lake, mesa }
+}
+
+// Perfil de dunas de un parche y su gradiente: n proyectada sobre el marco viento/cresta de su
+// centro, sierra asimétrica (barlovento suave, sotavento empinado) cuya fase ondula con ruido,
+// más ruido "ridged" estirado a lo largo de las crestas. Cada parche tiene su propia semilla.
+fn dune_patch(n: Vec3, cell: [i64;3], k: f32, seed: u64, p: &DesertParams) -> (f32, Vec3) {
+    let s = seed.wrapping_add(60);
+    let c = worley_point(cell, s).normalized();
+    let patch_seed = hash_lattice(&cell, s);
+    let up = if c.y.abs() > 0.999 { vec3(1.0, 0.0, 0.0) } else { vec3(0.0, 1.0, 0.0) };
+    let east = up.cross(c).normalized();
+    let north = c.cross(east);
+    let (ws, wc) = p.wind_dir.sin_cos();
+    let wind = east*wc + north*ws;
+    let ridge = c.cross(wind);
+    let along = n.dot(wind);   // en la dirección del viento
+    let across = n.dot(ridge); // a lo largo de las crestas
+    // gradiente respecto de n de un ruido evaluado en (along*kx, across*ky, 0)
+    let chain = |g: Vec3, kx: f32, ky: f32| wind * (g.x * kx) + ridge * (g.y * ky);
+
+    let (kx, ky) = (k*0.15, k*0.05);
+    let (warp, warp_grad) = fbm_grad(p.basis, vec3(along*kx, across*ky, 0.0), patch_seed.wrapping_add(61), 3, 2.0, 0.5);
+    let phase = (along*k + 2.5*warp).rem_euclid(1.0);
+    let phase_grad = wind * k + chain(warp_grad, kx, ky) * 2.5;
+    let (saw, slope) = if phase < 0.8 { (phase / 0.8, 1.0 / 0.8) } else { ((1.0 - phase) / 0.2, -1.0 / 0.2) };
+    let sharp = p.sharpness.max(0.1);
+    let crest = saw.powf(sharp);
+    let crest_grad = if saw > 0.0 { phase_grad * (slope * sharp * crest / saw) } else { vec3(0.0, 0.0, 0.0) };
+
+    let mut ridged = 0.0;
+    let mut ridged_grad = vec3(0.0, 0.0, 0.0);
+    let mut amp = 0.5;
+    let (mut kx, mut ky) = (k*0.7, k*0.12);
+    for o in 0..3 {
+        let (v, g) = noise3_grad(p.basis, vec3(along*kx, across*ky, 0.0), patch_seed.wrapping_add(62 + o));
+        let r = 1.0 - (2.0*v - 1.0).abs();
+        ridged += amp * r * r;
+        ridged_grad += chain(g, kx, ky) * (amp * 2.0 * r * -2.0 * (2.0*v - 1.0).signum());
+        kx *= 2.1;
+        ky *= 2.1;
+        amp *= 0.5;
+    }
+    let v = 0.75*crest + 0.35*ridged;
+    if v <= 0.0 || v >= 1.0 { return (saturate(v), vec3(0.0, 0.0, 0.0)); }
+    (v, crest_grad * 0.75 + ridged_grad * 0.35)
+}
+
+fn shade_moon(ctx: &ShadingCtx, p: &MoonParams) -> Color {
+    // 1) mares: cuencas bajas y oscuras de basalto que taparon los cráteres viejos
+    let m = fbm(p.basis, ctx.n * 1.6, ctx.seed.wrapping_add(71), 4, 2.0, 0.5);
+    let mare = smoothstep(0.62 - 0.3*p.maria, 0.58 - 0.3*p.maria, m);
+
+    // 2) cráteres y rayos de eyecta
+    let c = moon_craters(ctx.n, ctx.seed, p);
+    let mut albedo = p.c_highland.mix(p.c_mare, mare);
+    albedo = albedo * (0.9 + 0.2*fbm(p.basis, ctx.n * 40.0, ctx.seed.wrapping_add(72), 3, 2.0, 0.5));
+    albedo = albedo.mix(p.c_ray, saturate(c.rays * p.ray_ejecta));
+
+    // 3) relieve y regolito: Lommel–Seeliger, sin término ambiente (no hay atmósfera).
+    //    Normal con el gradiente analítico de los cráteres, aplanados en los mares (la máscara
+    //    cambia a escala de cuencas: se toma constante)
+    let flood = 1.0 - 0.7*mare;
+    let nb = bump_from_gradient(ctx.n, p.relief, c.grad * flood);
+    let light = (0.7*regolith(nb, ctx.v, ctx.l0) + 0.3*regolith(nb, ctx.v, ctx.l1)) * p.albedo;
+    (albedo * light).clamp01()
+}
+
+// Height and gradient are over the unit sphere direction `n`.
+fn moon_craters(n: Vec3, seed: u64, p: &MoonParams) -> Craters {
+    let mut c = craters(n * p.crater_freq, seed.wrapping_add(73), p.crater_octaves, p.size_power, p.density, p.rim_height);
+    let scale = 0.15 / p.crater_freq.max(0.1);
+    c.height *= scale;
+    c.grad = c.grad * (scale * p.crater_freq);
+    c
+}
+
+// Lommel–Seeliger: a single-scattering regolith reflects μ0/(μ0+μ), which is constant across
+// the disk when the sun is behind the viewer, so the full moon looks flat instead of shaded
+// towards the limb. Scaled by 2 to match Lambert at normal incidence, with a small
+// opposition surge.
+fn regolith(n: Vec3, v: Vec3, l: Vec3) -> f32 {
+    let mu0 = n.dot(l);
+    let mu = n.dot(v).max(0.0);
+    if mu0 <= 0.0 { return 0.0; }
+    let phase = saturate(v.dot(l)).acos();
+    let surge = 1.0 + 0.4*(-phase / 0.1).exp();
+    2.0 * mu0 / (mu0 + mu + 1e-4) * surge
+}
+
+fn shade_ice(ctx: &ShadingCtx, p: &IceParams) -> Color {
     let (lat, lon) = lat_lon_from_normal(ctx.n);
-    let m = (lon*2.0*PI*p.freq + p.marbling*fbm3(vec3(lat*p.freq, lon*p.freq, ctx.seed), 4, 2.0, 0.5)).sin()*0.5 + 0.5;
-    let cracks = ((m-0.65)/0.03).clamp(0.0,1.0);
+    let m = (lon*2.0*PI*p.freq + p.marbling*fbm(p.basis, vec3(lat*p.freq, lon*p.freq, 0.0), ctx.seed, 4, 2.0, 0.5)).sin()*0.5 + 0.5;
     let mut col = p.c_ice.mix(p.c_snow, m);
+
+    // grietas: bordes de celdas Worley, una red principal y otra fina más tenue
+    let q = ctx.n * p.crack_scale;
+    let wide = worley3(q, ctx.seed.wrapping_add(7), p.crack_metric);
+    let fine = worley3(q * 2.7, ctx.seed.wrapping_add(8), p.crack_metric);
+    let cracks = (1.0 - smoothstep(0.0, p.crack_width, wide.edge()))
+        .max(0.6 * (1.0 - smoothstep(0.0, p.crack_width * 0.6, fine.edge())));
     col = col.mix(p.c_crack, cracks);
 
     // luz del disco
     let nl = nl_mix(ctx.n, ctx.l0, ctx.l1);
     col = col * (0.5 + 0.5*nl);
+    col.clamp01()
+}
+
+// Cloud density in [0, 1] above the surface direction `n` (local, unit). Each latitude band
+// drifts with its own wind: eastward at the equator, westward in the mid latitudes, and so on.
+fn cloud_density(n: Vec3, t: f32, seed: u64, c: &CloudParams, octaves: u32) -> f32 {
+    // franjas de viento: las que van con el ecuador a `wind`, las de en medio a `wind_mid`
+    let lat = n.y.clamp(-1.0, 1.0).asin();
+    let band = (c.bands*lat).cos();
+    let wind = if band >= 0.0 { c.wind * band } else { -c.wind_mid * band };
+    let (s, co) = (wind*t).sin_cos();
+    let q = vec3(n.x*co - n.z*s, n.y, n.x*s + n.z*co) * c.scale;
+    // pequeño remolino para que no sean manchas redondas
+    let warp = fbm(c.basis, q*0.5, seed.wrapping_add(32), 2, 2.0, 0.5) - 0.5;
+    let d = fbm(c.basis, q + vec3(warp, 0.0, -warp)*1.5, seed.wrapping_add(31), octaves.min(12) as i32, 2.0, 0.5);
+    let thr = 1.0 - c.coverage;
+    smoothstep(thr - 0.08, thr + 0.12, d)
+}
 
-    // aire frío en rim
-    let rim = rim_term(ctx.n, ctx.v, 2.2);
-    col = col + common.cool*(0.12*rim);
+// Clouds over an already shaded surface: soft shadow cast along l0 onto the ground below,
+// then the cloud layer lit by l0/l1 on top.
+fn composite_clouds(ctx: &ShadingCtx, surface: Color, c: &CloudParams) -> (Color, f32) {
+    let octaves = c.octaves.max(1);
+    let nl0 = ctx.n.dot(ctx.l0);
+
+    // sombra: la nube que tapa l0, muestreada con menos octavas para un borde suave
+    let n_shadow = (ctx.n + ctx.l0 * (c.altitude / nl0.max(0.15))).normalized();
+    let shadow = cloud_density(n_shadow, ctx.t, ctx.seed, c, octaves.saturating_sub(2).max(1)) * saturate(nl0 * 4.0);
+    let ground = surface * (1.0 - 0.55*shadow);
+
+    // capa de nubes: paralaje por la altitud visto desde ctx.v
+    let n_cloud = (ctx.n + ctx.v * (c.altitude / ctx.n.dot(ctx.v).max(0.2))).normalized();
+    let d = cloud_density(n_cloud, ctx.t, ctx.seed, c, octaves);
+    let lit = nl_mix(n_cloud, ctx.l0, ctx.l1);
+    let cloud = c.color * ((0.45 + 0.55*lit) * (1.0 - 0.2*d));
+    (ground.mix(cloud, d), d)
+}
+
+// Atmosphere density multiplier of a body (0 = none); only Rocky and Ice have one.
+pub fn atmosphere_density(body: Body, params: &Params) -> f32 {
+    match body {
+        Body::Rocky => params.rocky.k_atm,
+        Body::Ice => params.ice.k_atm,
+        _ => 0.0,
+    }
+}
+
+// Single scattering (Rayleigh + Mie) along the view ray through the atmosphere shell.
+// Local units: planet radius 1, `d` unit; the segment ends at `t_end` (the surface or whatever
+// is in front). Returns (in-scattered light, transmittance) or None if the ray misses the shell.
+pub fn atmosphere(o: Vec3, d: Vec3, t_end: f32, l0: Vec3, l1: Vec3, p: &AtmosphereParams, density: f32) -> Option<(Color, Color)> {
+    const VIEW_STEPS: usize = 8;
+    let (t0, t1) = sphere_span(o, d, p.radius)?;
+    let (t0, t1) = (t0.max(0.0), t1.min(t_end));
+    if t1 <= t0 { return None; }
+
+    let beta_m = vec3(p.beta_mie, p.beta_mie, p.beta_mie);
+    // profundidad óptica (rayleigh, mie) por unidad de longitud a la altura de x
+    let dens = |x: Vec3| {
+        let h = (x.length() - 1.0).max(0.0);
+        ((-h / p.h_rayleigh).exp() * density, (-h / p.h_mie).exp() * density)
+    };
+    let extinction = |od: (f32, f32)| {
+        let tau = p.beta_rayleigh * od.0 + beta_m * (1.1 * od.1);
+        vec3((-tau.x).exp(), (-tau.y).exp(), (-tau.z).exp())
+    };
+    // profundidad óptica desde x hacia la luz (función de Chapman); None si el planeta la tapa
+    let to_light = |x: Vec3, l: Vec3| {
+        if sphere_span(x, l, 1.0).is_some_and(|(a, _)| a > 0.0) { return None; }
+        let r = x.length();
+        let mu = x.dot(l) / r;
+        Some((chapman(r, mu, p.h_rayleigh) * density, chapman(r, mu, p.h_mie) * density))
+    };
+    let phase_r = |mu: f32| 3.0 / (16.0 * PI) * (1.0 + mu*mu);
+    let phase_m = |mu: f32| {
+        let g = p.mie_g;
+        3.0 / (8.0 * PI) * ((1.0 - g*g) * (1.0 + mu*mu)) / ((2.0 + g*g) * (1.0 + g*g - 2.0*g*mu).powf(1.5))
+    };
+    // las fases solo dependen del ángulo entre rayo y luz: una vez por luz, no por muestra;
+    // con una sola luz (l0 = l1) se integra una vez con peso 1
+    let (lights, n) = if (l0 - l1).dot(l0 - l1) < 1e-12 { ([(l0, 1.0), (l1, 0.0)], 1) } else { ([(l0, 0.7), (l1, 0.3)], 2) };
+    let phases = lights.map(|(l, w)| {
+        let mu = d.dot(l);
+        (p.beta_rayleigh * (phase_r(mu) * w), beta_m * (phase_m(mu) * w))
+    });
+
+    let ds = (t1 - t0) / VIEW_STEPS as f32;
+    let mut od_view = (0.0, 0.0);
+    let mut sum = vec3(0.0, 0.0, 0.0);
+    for i in 0..VIEW_STEPS {
+        let x = o + d * (t0 + ds * (i as f32 + 0.5));
+        let (r, m) = dens(x);
+        od_view = (od_view.0 + r*ds*0.5, od_view.1 + m*ds*0.5);
+        for (&(l, _), &(pr, pm)) in lights.iter().zip(&phases).take(n) {
+            let Some(od_l) = to_light(x, l) else { continue };
+            let att = extinction((od_view.0 + od_l.0, od_view.1 + od_l.1));
+            sum += att * (pr * r + pm * m) * ds;
+        }
+        od_view = (od_view.0 + r*ds*0.5, od_view.1 + m*ds*0.5);
+    }
+    Some((sum * p.sun, extinction(od_view)))
+}
+
+// Column density exp(-h/H) integrated from radius r to infinity along a ray at cos(zenith) mu,
+// for a planet of radius 1 and scale height `scale` (Schüler's approximation of Chapman's function).
+fn chapman(r: f32, mu: f32, scale: f32) -> f32 {
+    let x = 1.0 / scale;
+    let h = (r - 1.0).max(0.0) / scale;
+    let c = (x + h).sqrt();
+    let ch = if mu >= 0.0 {
+        c / (c*mu + 1.0) * (-h).exp()
+    } else {
+        let x0 = (1.0 - mu*mu).sqrt() * (x + h);
+        2.0 * x0.sqrt() * (x - x0).exp() - c / (1.0 - c*mu) * (-h).exp()
+    };
+    ch * scale
+}
+
+// Entry and exit distances of a ray through a sphere centered at the origin (d unit).
+fn sphere_span(o: Vec3, d: Vec3, r: f32) -> Option<(f32, f32)> {
+    let b = o.dot(d);
+    let c = o.dot(o) - r*r;
+    let disc = b*b - c;
+    if disc < 0.0 { return None; }
+    let s = disc.sqrt();
+    if -b + s <= 0.0 { return None; }
+    Some((-b - s, -b + s))
+}
+
+// Opacity of the ring plane at radius r (body radii): faint inner ring, dense middle, the gap,
+// then a thinner outer ring, all broken into ringlets.
+pub fn ring_density(r: f32, p: &RingParams, seed: u64) -> f32 {
+    let u = (r - p.rin) / (p.rout - p.rin);
+    if !(0.0..=1.0).contains(&u) { return 0.0; }
+    let profile = mix(0.25, 1.0, smoothstep(0.0, 0.3, u)) * mix(1.0, 0.6, smoothstep(p.gap_pos, 1.0, u));
+    let gap = smoothstep(0.0, p.gap_width*0.5, (u - p.gap_pos).abs() - p.gap_width*0.5);
+    let edges = smoothstep(0.0, 0.02, u) * smoothstep(1.0, 0.98, u);
+    let lets = fbm(p.basis, vec3(r*p.ringlets, 0.0, 0.0), seed.wrapping_add(21), 4, 2.0, 0.55);
+    saturate(p.opacity * profile * gap * edges * (0.45 + 0.9*lets))
+}
+
+// Ring plane at ctx.p (local, y = 0): (color, alpha). Lit from whichever side the light is on,
+// the unlit face only shows light scattered through the particles.
+pub fn shade_ring(ctx: &ShadingCtx, p: &RingParams) -> (Color, f32) {
+    let r = (ctx.p.x*ctx.p.x + ctx.p.z*ctx.p.z).sqrt();
+    let alpha = ring_density(r, p, ctx.seed);
+    let u = saturate((r - p.rin) / (p.rout - p.rin));
+    let bands = fbm(p.basis, vec3(r*p.ringlets*0.25, 0.0, 0.0), ctx.seed.wrapping_add(22), 3, 2.0, 0.5);
+    let base = p.c_inner.mix(p.c_outer, saturate(u + 0.5*(bands - 0.5)));
+
+    let same_side = |l: Vec3| if l.y * ctx.v.y > 0.0 { 1.0 } else { 1.0 - alpha };
+    let lit = 0.7*ctx.l0.y.abs()*same_side(ctx.l0) + 0.3*ctx.l1.y.abs()*same_side(ctx.l1);
+    (base * (0.35 + 0.65*saturate(lit*4.0)), alpha)
+}
+
+// Emissive: lights are ignored, the star is one.
+fn shade_star(ctx: &ShadingCtx, p: &StarParams) -> Color {
+    let mu = saturate(ctx.n.dot(ctx.v));
+
+    // 1) granulación: celdas Worley que hierven lentamente, centros calientes y bordes oscuros
+    let q = ctx.n * p.granulation;
+    let boil = vec3(0.0, ctx.t*0.03, ctx.t*0.02);
+    let cells = worley3(q + boil, ctx.seed, Metric::Euclidean);
+    let lanes = smoothstep(0.0, 0.35, cells.edge());
+    let fine = fbm(p.basis, q*2.5 - boil, ctx.seed.wrapping_add(1), 3, 2.0, 0.5);
+    let gran = 0.86 + 0.08*lanes + 0.1*(fine - 0.5) + 0.06*(cells.rand - 0.5);
+
+    // 2) manchas: ruido 4D (deriva con t) en las latitudes activas, umbra y penumbra
+    let (s, _) = simplex4([ctx.n.x*2.2, ctx.n.y*2.2, ctx.n.z*2.2, ctx.t*0.04], ctx.seed.wrapping_add(2));
+    let belt = smoothstep(0.65, 0.2, ctx.n.y.abs());
+    let spot = (s*0.5 + 0.5) * belt;
+    let thr = 1.0 - 0.45*saturate(p.spot_density);
+    let penumbra = smoothstep(thr - 0.06, thr, spot);
+    let umbra = smoothstep(thr + 0.02, thr + 0.06, spot);
+    let temp = p.temperature * (1.0 - 0.15*penumbra - 0.25*umbra);
+
+    // 3) fáculas y fulguraciones: parches brillantes que destacan hacia el limbo
+    let f = fbm(p.basis, ctx.n*6.0 + vec3(ctx.t*0.2, 0.0, 0.0), ctx.seed.wrapping_add(3), 3, 2.0, 0.5);
+    let flare = smoothstep(0.72 - 0.12*p.flare_activity, 0.9, f) * p.flare_activity * (1.0 - penumbra);
+
+    // 4) oscurecimiento hacia el limbo (ley cuadrática, valores solares) y limbo más rojo
+    let k = 1.0 - mu;
+    let limb = 1.0 - 0.47*k - 0.23*k*k;
+    let rel = temp / p.temperature;
+    let col = blackbody(temp * (0.9 + 0.1*mu)) * (rel*rel*rel*rel * limb * gran);
+    col + blackbody(temp * 1.3) * (flare * (0.3 + 0.7*k))
+}
+
+// Corona seen outside the silhouette. ctx.p is the point of closest approach of the view ray
+// in star radii (|p| > 1); the result is added over whatever lies behind.
+pub fn shade_corona(ctx: &ShadingCtx, p: &StarParams) -> Color {
+    let d = ctx.p.length();
+    let h = (d - 1.0).max(0.0);
+    let dir = ctx.p / d.max(1e-6);
+
+    // halo interno brillante + cola larga
+    let glow = 0.6*(-h*7.0).exp() + 0.2/(1.0 + h*h*12.0);
+
+    // serpentinas: ruido sobre la dirección, estirado radialmente y girando despacio
+    let a = Mat3::rotation_y(ctx.t*0.02).mul_vec(dir) * 3.0;
+    let streams = fbm(p.basis, a + vec3(0.0, 0.0, h*0.3), ctx.seed.wrapping_add(4), 4, 2.0, 0.5);
+    let equator = 0.6 + 0.4*(1.0 - dir.y.abs());
+    let rays = (0.5 + 1.2*streams*streams) * equator;
+
+    let tint = blackbody(p.temperature).mix(rgb(1.0, 1.0, 1.0), 0.5);
+    (tint * (glow * rays * (0.5 + p.flare_activity))).clamp01()
+}
+
+// Radius of a star-shaped surface along the unit direction `n`, for the bodies that are not
+// plain spheres (the body's sphere only bounds them); 1 for the rest.
+pub fn surface_radius(n: Vec3, body: Body, params: &Params, seed: u64) -> f32 {
+    match body {
+        Body::Comet => nucleus_radius(n, seed, &params.comet),
+        Body::Asteroid => asteroid_radius(n, seed, &params.asteroid),
+        _ => 1.0,
+    }
+}
+
+// Cheap estimate of surface_radius and how far the real radius can be from it, for marching.
+pub fn surface_radius_coarse(n: Vec3, body: Body, params: &Params, seed: u64) -> (f32, f32) {
+    match body {
+        Body::Comet => nucleus_radius_coarse(n, seed, &params.comet),
+        Body::Asteroid => asteroid_radius_coarse(n, seed, &params.asteroid),
+        _ => (1.0, 0.0),
+    }
+}
+
+// A 4-octave fbm from its first two octaves: the two dropped ones add between 0 and 0.1875,
+// so (estimate, slack) = (two octaves + 0.09375, 0.09375).
+fn fbm4_coarse(basis: NoiseBasis, p: Vec3, seed: u64) -> (f32, f32) {
+    (fbm(basis, p, seed, 2, 2.0, 0.5) + 0.09375, 0.09375)
+}
+
+// Asteroid shape: a sphere stretched along a per-seed axis and dented by low-frequency fbm and
+// craters; always within the unit bounding sphere.
+pub fn asteroid_radius(n: Vec3, seed: u64, p: &AsteroidParams) -> f32 {
+    let stretch = asteroid_stretch(n, seed, p);
+    let lumps = fbm(p.basis, n * p.lump_freq, seed.wrapping_add(91), 4, 2.0, 0.5);
+    let pits = crater_profile(n * p.crater_freq, seed.wrapping_add(92)) * 0.06 * p.crater_depth;
+    (stretch * (1.0 - p.roughness * lumps) + pits).clamp(0.2, 1.0)
+}
+
+// asteroid_radius with two lump octaves and no craters, and the most it can be off: the
+// dropped octaves, and the crater profile in [-0.7, 0.5] taken at its middle.
+pub fn asteroid_radius_coarse(n: Vec3, seed: u64, p: &AsteroidParams) -> (f32, f32) {
+    let stretch = asteroid_stretch(n, seed, p);
+    let (lumps, slack) = fbm4_coarse(p.basis, n * p.lump_freq, seed.wrapping_add(91));
+    let r = stretch * (1.0 - p.roughness * lumps) - 0.006 * p.crater_depth;
+    (r.clamp(0.2, 1.0), stretch * p.roughness.abs() * slack + 0.036 * p.crater_depth.abs())
+}
+
+// Stretch along the per-seed long axis.
+fn asteroid_stretch(n: Vec3, seed: u64, p: &AsteroidParams) -> f32 {
+    let k = vec3(hash31([1, 0, 0], seed) - 0.5, hash31([2, 0, 0], seed) - 0.5, hash31([3, 0, 0], seed) - 0.5).normalized();
+    let along = n.dot(k);
+    1.0 - p.elongation * (1.0 - along*along) * 0.5
+}
+
+fn shade_asteroid(ctx: &ShadingCtx, p: &AsteroidParams) -> Color {
+    // 1) normal de la forma (bultos y cráteres) con algo de grava fina
+    let nb = bump_normal(ctx.n, 1.0, |n| {
+        asteroid_radius(n, ctx.seed, p) + 0.015*fbm(p.basis, n*16.0, ctx.seed.wrapping_add(93), 3, 2.0, 0.5)
+    });
+
+    // 2) roca y polvo: el polvo se acumula en lo bajo
+    let h = asteroid_radius(ctx.n, ctx.seed, p);
+    let patches = fbm(p.basis, ctx.n*4.0, ctx.seed.wrapping_add(94), 3, 2.0, 0.5);
+    let albedo = p.c_rock.mix(p.c_dust, saturate(patches + (0.8 - h)));
+
+    // 3) regolito sin atmósfera, como la luna
+    let light = (0.7*regolith(nb, ctx.v, ctx.l0) + 0.3*regolith(nb, ctx.v, ctx.l1)) * p.albedo;
+    (albedo * light).clamp01()
+}
+
+// Radius of the comet nucleus along the unit direction `n`: a lumpy, elongated potato inside
+// the unit bounding sphere.
+pub fn nucleus_radius(n: Vec3, seed: u64, p: &CometParams) -> f32 {
+    let lumps = fbm(p.basis, n * 1.3, seed.wrapping_add(81), 4, 2.0, 0.5);
+    let waist = 1.0 - 0.25*p.roughness*(1.0 - n.x*n.x);
+    (waist - p.roughness*1.1*lumps).clamp(0.2, 1.0)
+}
+
+// nucleus_radius with two lump octaves, and the most it can be off.
+pub fn nucleus_radius_coarse(n: Vec3, seed: u64, p: &CometParams) -> (f32, f32) {
+    let (lumps, slack) = fbm4_coarse(p.basis, n * 1.3, seed.wrapping_add(81));
+    let waist = 1.0 - 0.25*p.roughness*(1.0 - n.x*n.x);
+    ((waist - p.roughness*1.1*lumps).clamp(0.2, 1.0), p.roughness.abs()*1.1*slack)
+}
+
+fn shade_nucleus(ctx: &ShadingCtx, p: &CometParams) -> Color {
+    // 1) normal de la superficie desplazada y rugosidad fina
+    let nb = bump_normal(ctx.n, 1.0, |n| {
+        nucleus_radius(n, ctx.seed, p) + 0.02*fbm(p.basis, n*12.0, ctx.seed.wrapping_add(82), 3, 2.0, 0.5)
+    });
+
+    // 2) hielo sucio muy oscuro con manchas algo más claras
+    let frost = fbm(p.basis, ctx.n*5.0, ctx.seed.wrapping_add(83), 3, 2.0, 0.5);
+    let albedo = p.c_nucleus * (0.7 + 0.6*frost);
+    (albedo * (0.05 + 0.95*nl_mix(nb, ctx.l0, ctx.l1))).clamp01()
+}
+
+// Activity at `dist` from the main light: (tail length scale, brightness).
+pub fn comet_activity(dist: f32, p: &CometParams) -> (f32, f32) {
+    let a = (p.ref_distance / dist.max(1e-3)).min(4.0);
+    (a, a*a)
+}
+
+// Light from the coma and tails along the view ray, added over what lies behind (thin gas and
+// dust, no absorption). Local units: nucleus radius 1; ctx.p is the ray origin, -ctx.v its
+// direction and ctx.l0 the direction towards the main light. The segment ends at `t_end`.
+pub fn comet_glow(ctx: &ShadingCtx, t_end: f32, activity: (f32, f32), p: &CometParams) -> Color {
+    const STEPS: usize = 96;
+    let (o, d) = (ctx.p, -ctx.v);
+    let (len_scale, bright) = activity;
+    let tail = -ctx.l0;
+    // la cola de polvo se queda atrás en la órbita (el plano XZ local): se curva dentro de él
+    let bend = tail.cross(vec3(0.0, 1.0, 0.0));
+    let bend = if bend.length() > 1e-3 { bend.normalized() } else { vec3(1.0, 0.0, 0.0) };
+
+    // 1) coma: densidad 1/(r0² + r²), integrada analíticamente a lo largo del rayo
+    let r0 = 1.5;
+    let tc = -o.dot(d);
+    let b2 = (o + d*tc).dot(o + d*tc);
+    let c = (r0*r0 + b2).sqrt();
+    let (t0, t1) = (0.0f32, t_end.min(tc + 4.0*p.coma_radius));
+    let column = if t1 > t0 { ((t1 - tc) / c).atan() - ((t0 - tc) / c).atan() } else { 0.0 };
+    let fade = (-b2 / (p.coma_radius*p.coma_radius)).exp();
+    let mut col = p.c_coma * (column / c * fade * p.coma_brightness * bright * 0.7);
+
+    let length = p.tail_length * len_scale;
+    let start = |s: f32| smoothstep(0.0, 0.3*p.coma_radius, s);
+
+    // 2) iones: recta en el viento solar y estrecha; se integra como un cilindro gaussiano en
+    // el punto del rayo más cercano al eje, con filamentos que se alejan con t
+    let cos_a = d.dot(tail);
+    let sin_a = (1.0 - cos_a*cos_a).max(0.0).sqrt();
+    let tx = if sin_a > 1e-4 { (cos_a * tail.dot(o) - d.dot(o)) / (sin_a*sin_a) } else { 0.0 };
+    let x = o + d * tx.clamp(0.0, t_end.min(1e6));
+    let s = x.dot(tail);
+    if s > 0.0 {
+        let q = x - tail*s;
+        let w = 1.2 + 0.04*s;
+        let strands = fbm(p.basis, q*(0.6 / w) + tail*(s*0.02 - ctx.t*0.4), ctx.seed.wrapping_add(84), 3, 2.0, 0.5);
+        let column = (PI.sqrt() * w / sin_a.max(1e-4)).min(length);
+        let ion = (-(q.dot(q)) / (w*w)).exp() * (-s / length).exp() * start(s) * (0.4 + 1.2*strands) * column;
+        col += p.c_ion * (ion * 0.15 * p.ion_brightness * bright);
+    }
+
+    // 3) polvo: ancha y curvada (la desviación crece con s²); se marcha dentro de una esfera
+    // que envuelve la curva
+    let center = tail * length + bend * (p.dust_curvature * length);
+    let Some((s0, s1)) = sphere_span(o - center, d, length * 1.5) else { return col.clamp01() };
+    let (s0, s1) = (s0.max(0.0), s1.min(t_end));
+    if s1 <= s0 { return col.clamp01(); }
+    let ds = (s1 - s0) / STEPS as f32;
+    let mut dust = 0.0;
+    for i in 0..STEPS {
+        let x = o + d * (s0 + ds * (i as f32 + 0.5));
+        let s = x.dot(tail);
+        if s <= 0.0 { continue; }
+        let q = x - tail*s - bend*(p.dust_curvature * s*s / length);
+        let w = 3.0 + 0.15*s;
+        dust += (-(q.dot(q)) / (w*w)).exp() * (-s / (0.7*length)).exp() * start(s);
+    }
+    col += p.c_dust * (dust * ds * 0.06 * p.dust_brightness * bright);
     col.clamp01()
-}
\ No newline at end of file
+}
+
+// Sky along the world direction `d`: point stars, the Milky Way and emission nebulae. It only
+// depends on `d` and the params, so it stays fixed to the world as the camera orbits.
+pub fn background(d: Vec3, p: &BackgroundParams) -> Color {
+    let pole = p.pole.normalized();
+    let seed = p.seed;
+
+    // 1) Vía Láctea: banda en el ecuador galáctico, bulbo hacia el centro y carriles de polvo
+    let b = d.dot(pole);
+    let x = vec3(1.0, 0.0, 0.0);
+    let center = if pole.x.abs() < 0.99 { (x - pole*pole.x).normalized() } else { vec3(0.0, 0.0, 1.0) };
+    let band = (-(b / 0.2).powi(2)).exp();
+    // cada capa apagada (peso 0) se salta entera: sus fbm son lo caro del fondo
+    let (mut milky, mut dust) = (rgb(0.0, 0.0, 0.0), 0.0);
+    if p.milky_way > 0.0 {
+        let bulge = (-(d - center).dot(d - center) / 0.25).exp() * (-(b / 0.35).powi(2)).exp();
+        // grumos y polvo solo cerca de la banda, donde se notan
+        let clumps = if band > 1e-4 { fbm(NoiseBasis::Value, d*7.0, seed.wrapping_add(1), 5, 2.0, 0.5) } else { 0.0 };
+        let lane_band = (-(b / 0.06).powi(2)).exp();
+        if lane_band > 1e-4 {
+            dust = smoothstep(0.45, 0.65, fbm(NoiseBasis::Value, d*11.0, seed.wrapping_add(2), 4, 2.0, 0.5)) * lane_band * 0.85;
+        }
+        let glow = (band * (0.3 + 0.9*clumps*clumps) + 1.5*bulge) * (1.0 - dust);
+        milky = rgb(1.0, 0.92, 0.8) * (glow * 0.25 * p.milky_way);
+    }
+
+    // 2) nebulosas: capas de fbm a lo largo del rayo que emiten y tapan lo de atrás
+    let mut neb = rgb(0.0, 0.0, 0.0);
+    let mut trans = 1.0;
+    if p.nebulae > 0.0 {
+        let mask = smoothstep(0.5, 0.68, fbm(NoiseBasis::Value, d*1.3, seed.wrapping_add(3), 3, 2.0, 0.5));
+        if mask > 0.0 {
+            for i in 0..6 {
+                let x = d * (p.nebula_scale * (1.0 + 0.12*i as f32));
+                let gas = saturate(fbm(NoiseBasis::Value, x, seed.wrapping_add(4), 6, 2.1, 0.6) - 0.45) * mask * 3.0;
+                let hue = fbm(NoiseBasis::Value, x*0.7, seed.wrapping_add(5), 3, 2.0, 0.5);
+                let emit = p.c_nebula1.mix(p.c_nebula2, smoothstep(0.35, 0.65, hue));
+                neb += emit * (gas * trans * 0.15 * p.nebulae);
+                trans *= 1.0 - 0.15*gas;
+            }
+        }
+    }
+
+    // 3) estrellas, más densas en la banda
+    let stars = starfield(d, p, 1.0 + 3.0*band) * (1.0 - dust);
+    stars * trans + milky * trans + neb
+}
+
+// Point stars on a grid of cells over the sky; each cell may hold one star with a power-law
+// flux (N(>F) ∝ F^-1.5, many faint and few bright) and a blackbody color.
+fn starfield(d: Vec3, p: &BackgroundParams, density: f32) -> Color {
+    const FREQ: f32 = 240.0; // celdas por radián
+    const WIDTH: f32 = 0.12; // radio de una estrella, en celdas
+    if p.stars <= 0.0 { return rgb(0.0, 0.0, 0.0); }
+    let q = d * FREQ;
+    let c = [q.x.floor() as i64, q.y.floor() as i64, q.z.floor() as i64];
+    let mut col = rgb(0.0, 0.0, 0.0);
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let cell = [c[0] + dx, c[1] + dy, c[2] + dz];
+                let h = hash_lattice(&cell, p.seed);
+                if unit_f32(h) >= p.star_density * density { continue; }
+                let at = worley_point(cell, p.seed ^ 0x5bd1_e995);
+                let dist = (at.normalized() - d).length() * FREQ;
+                if dist > 4.0*WIDTH { continue; }
+                let flux = unit_f32(h.rotate_left(24)).max(1e-3).powf(-2.0 / 3.0).min(60.0);
+                let temp = 3000.0 * (1.0 + 8.0*unit_f32(h.rotate_left(48)).powi(4));
+                col += blackbody(temp) * ((-(dist / WIDTH).powi(2)).exp() * flux * 0.12 * p.stars);
+            }
+        }
+    }
+    col
+}